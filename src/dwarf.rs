@@ -1,129 +1,578 @@
 // Parses DWARF information.
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::prelude::*;
-use std::io::{self, BufReader};
+use std::ops::Range;
+use std::result;
 
 use gimli;
 
-use gimli::{DebugAbbrev, DebugInfo, DebugLine, DebugStr, LittleEndian};
+use gimli::{EndianSlice, LittleEndian};
 
-trait Reader: gimli::Reader<Offset = usize> {}
+use error::{Error, Result, ResultExt, MODULE};
+use wasm_read::DebugSections;
 
-impl<'input, Endian> Reader for gimli::EndianBuf<'input, Endian> where Endian: gimli::Endianity {}
+type Slice<'a> = EndianSlice<'a, LittleEndian>;
 
-use wasm_read::DebugSections;
+// `C:\` or `C:/`, as found in objects built on Windows.
+fn has_drive(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes[2] == b'/' || bytes[2] == b'\\')
+}
 
-fn to_vec(b: &[u8]) -> Vec<u8> {
-    let mut result = Vec::new();
-    result.extend_from_slice(b);
+fn is_windows_absolute(path: &str) -> bool {
+    has_drive(path) || path.starts_with("\\\\")
+}
+
+fn is_absolute(path: &str) -> bool {
+    path.starts_with('/') || is_windows_absolute(path)
+}
+
+// Joins the paths, each one relative to the previous ones unless it is
+// absolute, and collapses `.` and `..` components. Windows paths keep their
+// root and use `/` separators, so that `C:\src\a.c` and `C:/src/./a.c` end up
+// the same.
+fn join_paths(paths: &[&str]) -> String {
+    let paths = match paths.iter().rposition(|path| is_absolute(path)) {
+        Some(start) => &paths[start..],
+        None => paths,
+    };
+    let windows = paths.first().is_some_and(|path| is_windows_absolute(path));
+    let mut root = "";
+    let mut components: Vec<&str> = Vec::new();
+    for (i, path) in paths.iter().enumerate() {
+        let mut path = *path;
+        if i == 0 {
+            let root_len = if has_drive(path) {
+                3
+            } else if path.starts_with("\\\\") {
+                2
+            } else if path.starts_with('/') {
+                1
+            } else {
+                0
+            };
+            root = &path[..root_len];
+            path = &path[root_len..];
+        }
+        for component in path.split(|c| c == '/' || (windows && c == '\\')) {
+            match component {
+                "" | "." => {}
+                ".." => {
+                    if components.last().is_some_and(|last| *last != "..") {
+                        components.pop();
+                    } else if root.is_empty() {
+                        components.push(component);
+                    }
+                }
+                _ => components.push(component),
+            }
+        }
+    }
+    let mut result = if windows {
+        root.replace('\\', "/")
+    } else {
+        String::from(root)
+    };
+    result += &components.join("/");
+    if result.is_empty() {
+        result.push('.');
+    }
     result
 }
 
+// Resolves the file name against its include directory and the compilation
+// directory of the unit. The strings may live in .debug_str, .debug_line_str
+// or inline in the line program header. Directory 0 is already the
+// compilation directory: gimli returns it for DWARF 4 and older, and DWARF 5
+// stores it there.
+fn file_path(
+    dwarf: &gimli::Dwarf<Slice>,
+    unit: &gimli::Unit<Slice>,
+    header: &gimli::LineProgramHeader<Slice>,
+    file: &gimli::FileEntry<Slice>,
+) -> result::Result<String, gimli::Error> {
+    let path_name = dwarf.attr_string(unit, file.path_name())?;
+    let directory = match file.directory(header) {
+        Some(directory) => Some(dwarf.attr_string(unit, directory)?),
+        None => None,
+    };
+    let comp_dir = unit.comp_dir.map(|dir| dir.to_string_lossy());
+    let directory = directory.map(|dir| dir.to_string_lossy());
+    let path_name = path_name.to_string_lossy();
+    let mut paths: Vec<&str> = Vec::new();
+    if file.directory_index() != 0 && directory != comp_dir {
+        paths.extend(comp_dir.as_deref());
+    }
+    paths.extend(directory.as_deref());
+    paths.push(&path_name);
+    Ok(join_paths(&paths))
+}
+
+// Maps the file index of the unit's line program to an index into sources.
+// Units share the entries of the files they have in common.
+fn source_id(
+    dwarf: &gimli::Dwarf<Slice>,
+    unit: &gimli::Unit<Slice>,
+    header: &gimli::LineProgramHeader<Slice>,
+    file_index: u64,
+    sources: &mut Vec<String>,
+    path_to_id_map: &mut HashMap<String, usize>,
+    source_to_id_map: &mut HashMap<u64, usize>,
+) -> result::Result<usize, gimli::Error> {
+    Ok(match source_to_id_map.entry(file_index) {
+        Entry::Occupied(entry) => *entry.get(),
+        Entry::Vacant(entry) => {
+            let file_path = match header.file(file_index) {
+                Some(file) => file_path(dwarf, unit, header, file)?,
+                None => String::from("<unknown>"),
+            };
+            let id = *path_to_id_map.entry(file_path).or_insert_with_key(|path| {
+                sources.push(path.clone());
+                sources.len() - 1
+            });
+            *entry.insert(id)
+        }
+    })
+}
+
+// Longest chain of DW_AT_specification and DW_AT_abstract_origin followed
+// by `die_name`. Real chains have at most a few links, from an inlined
+// instance to its abstract origin and on to the declaration; malformed
+// DWARF can make a cycle.
+const MAX_NAME_REFERENCES: usize = 16;
+
+// Name of the subprogram, following DW_AT_specification and
+// DW_AT_abstract_origin for definitions that do not repeat it.
+fn die_name(
+    dwarf: &gimli::Dwarf<Slice>,
+    unit: &gimli::Unit<Slice>,
+    entry: &gimli::DebuggingInformationEntry<Slice>,
+) -> result::Result<Option<String>, gimli::Error> {
+    let mut entry = entry.clone();
+    for _ in 0..=MAX_NAME_REFERENCES {
+        for attr in [gimli::DW_AT_name, gimli::DW_AT_linkage_name].iter() {
+            if let Some(value) = entry.attr_value(*attr)? {
+                let name = dwarf.attr_string(unit, value)?;
+                return Ok(Some(name.to_string_lossy().into_owned()));
+            }
+        }
+        let mut origin = None;
+        for attr in [gimli::DW_AT_specification, gimli::DW_AT_abstract_origin].iter() {
+            if let Some(gimli::AttributeValue::UnitRef(offset)) = entry.attr_value(*attr)? {
+                origin = Some(offset);
+                break;
+            }
+        }
+        match origin {
+            Some(offset) => entry = unit.entry(offset)?,
+            None => break,
+        }
+    }
+    Ok(None)
+}
+
+// Address range of a function, with an index into DebugLocInfo::names.
+struct FunctionRange {
+    begin: u64,
+    end: u64,
+    name_id: u32,
+}
+
+/// An address range of an inlined function instance and its call site.
+pub struct InlinedCall {
+    pub begin: u64,
+    pub end: u64,
+    /// Index into `DebugLocInfo::names` of the inlined function.
+    pub name_id: Option<u32>,
+    /// Index into `DebugLocInfo::sources` of the call site.
+    pub call_source_id: Option<u32>,
+    /// 1-based call site line, or 0 when unknown.
+    pub call_line: u32,
+    /// 1-based call site column, or 0 when unknown.
+    pub call_column: u32,
+    /// Number of inlined instances this one is nested in.
+    pub depth: u32,
+}
+
+/// A row of the DWARF line table.
 pub struct DebugLoc {
+    /// Offset of the instruction in the wasm module.
     pub address: u64,
+    /// Index into `DebugLocInfo::sources`.
     pub source_id: u32,
+    /// 1-based line, or 0 when the instruction has no source line.
     pub line: u32,
+    /// 1-based column, or 0 for the left edge.
     pub column: u32,
+    /// Index into `DebugLocInfo::names` of the enclosing function.
+    pub name_id: Option<u32>,
+    /// The row marks the first address past the end of a sequence.
+    pub end_sequence: bool,
 }
 
+/// Line information of a wasm module.
 pub struct DebugLocInfo {
+    /// Source file paths as recorded in the line programs.
     pub sources: Vec<String>,
+    /// Function names from the `DW_TAG_subprogram` entries.
+    pub names: Vec<String>,
+    /// Line table rows sorted by address; end of sequence rows go first
+    /// among rows with the same address.
     pub locations: Vec<DebugLoc>,
+    /// Inlined function instances sorted by address.
+    pub inlined_calls: Vec<InlinedCall>,
 }
 
-pub fn get_debug_loc(debug_sections: &DebugSections) -> DebugLocInfo {
+impl DebugLocInfo {
+    /// The line table row that covers the address, if any.
+    pub fn lookup(&self, address: u64) -> Option<&DebugLoc> {
+        let next = self.locations.partition_point(|loc| loc.address <= address);
+        if next == 0 {
+            return None;
+        }
+        let loc = &self.locations[next - 1];
+        if loc.end_sequence {
+            None
+        } else {
+            Some(loc)
+        }
+    }
+
+    /// Code ranges generated for a source position. `file` matches a source
+    /// path exactly or as its trailing components; without `column` every
+    /// column of the line matches. Adjacent ranges are merged.
+    pub fn address_ranges(&self, file: &str, line: u32, column: Option<u32>) -> Vec<Range<u64>> {
+        let suffix = format!("/{}", file.trim_start_matches('/'));
+        let source_matches: Vec<bool> = self
+            .sources
+            .iter()
+            .map(|source| source == file || source.ends_with(&suffix))
+            .collect();
+        let mut ranges: Vec<Range<u64>> = Vec::new();
+        for (loc, next) in self.locations.iter().zip(self.locations.iter().skip(1)) {
+            // A row spans up to the next row; of several rows at the same
+            // address only the last one covers code, as in `lookup`.
+            if loc.end_sequence
+                || next.address == loc.address
+                || !source_matches[loc.source_id as usize]
+                || loc.line != line
+                || column.is_some_and(|column| loc.column != column)
+            {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(last) if last.end == loc.address => last.end = next.address,
+                _ => ranges.push(loc.address..next.address),
+            }
+        }
+        ranges
+    }
+
+    /// Sweep over the inlined calls, to find those of increasing addresses.
+    pub fn inline_stacks(&self) -> InlineStacks<'_> {
+        InlineStacks::new(&self.inlined_calls)
+    }
+}
+
+/// Inlined calls containing each of a series of non-decreasing addresses,
+/// tracked in a single pass over the calls sorted by address.
+pub struct InlineStacks<'a> {
+    inlined_calls: &'a [InlinedCall],
+    next_call: usize,
+    active_calls: Vec<&'a InlinedCall>,
+}
+
+impl<'a> InlineStacks<'a> {
+    fn new(inlined_calls: &'a [InlinedCall]) -> InlineStacks<'a> {
+        InlineStacks {
+            inlined_calls,
+            next_call: 0,
+            active_calls: Vec::new(),
+        }
+    }
+
+    /// Inlined calls that contain the address, innermost first. The address
+    /// must not be lower than the one of the previous call.
+    pub fn at(&mut self, address: u64) -> Vec<&'a InlinedCall> {
+        let inlined_calls = self.inlined_calls;
+        while self.next_call < inlined_calls.len() && inlined_calls[self.next_call].begin <= address
+        {
+            self.active_calls.push(&inlined_calls[self.next_call]);
+            self.next_call += 1;
+        }
+        self.active_calls.retain(|call| address < call.end);
+        let mut stack = self.active_calls.clone();
+        stack.sort_by_key(|call| Reverse(call.depth));
+        stack
+    }
+}
+
+/// Decodes the line programs of all compilation units. A module without
+/// `.debug_info` or `.debug_line` has no line information, and results in
+/// empty `DebugLocInfo`.
+pub fn get_debug_loc(debug_sections: &DebugSections) -> Result<DebugLocInfo> {
     let mut sources = Vec::new();
+    let mut names = Vec::new();
     let mut locations = Vec::new();
+    let mut path_to_id_map: HashMap<String, usize> = HashMap::new();
     let mut source_to_id_map: HashMap<u64, usize> = HashMap::new();
+    let mut name_to_id_map: HashMap<String, u32> = HashMap::new();
+    let mut functions = Vec::new();
+    let mut inlined_calls = Vec::new();
 
-    let ref tables = debug_sections.tables;
-    let ref debug_str = DebugStr::new(&tables[".debug_str"], LittleEndian);
-    let ref debug_abbrev = DebugAbbrev::new(&tables[".debug_abbrev"], LittleEndian);
-    let ref debug_info = DebugInfo::new(&tables[".debug_info"], LittleEndian);
-    let ref debug_line = DebugLine::new(&tables[".debug_line"], LittleEndian);
-
-    let mut iter = debug_info.units();
-    while let Some(unit) = iter.next().unwrap_or(None) {
-        let abbrevs = unit.abbreviations(debug_abbrev).unwrap();
-        let mut cursor = unit.entries(&abbrevs);
-        cursor.next_dfs().expect("???");
-        let root = cursor.current().expect("missing die");
-        let offset = match root.attr_value(gimli::DW_AT_stmt_list).unwrap() {
-            Some(gimli::AttributeValue::DebugLineRef(offset)) => offset,
-            _ => continue,
+    let tables = &debug_sections.tables;
+    if !(tables.contains_key(".debug_info") && tables.contains_key(".debug_line")) {
+        return Ok(DebugLocInfo {
+            sources,
+            names,
+            locations,
+            inlined_calls,
+        });
+    }
+    if !tables.contains_key(".debug_abbrev") {
+        return Err(Error::MissingSection(String::from(".debug_abbrev")));
+    }
+    // Absent sections are empty: e.g. there is no .debug_str when all strings
+    // are inline, and .debug_line_str and .debug_str_offsets are DWARF 5 only.
+    let load_section = |id: gimli::SectionId| -> result::Result<Slice, gimli::Error> {
+        let data = tables.get(id.name()).map_or(&[][..], |data| &data[..]);
+        Ok(EndianSlice::new(data, LittleEndian))
+    };
+    let dwarf = gimli::Dwarf::load(load_section).in_section(MODULE, 0)?;
+
+    let mut iter = dwarf.units();
+    while let Some(header) = iter.next().in_section(".debug_info", 0)? {
+        let unit_offset = header
+            .offset()
+            .as_debug_info_offset()
+            .map_or(0, |offset| offset.0);
+        let unit = dwarf.unit(header).in_section(".debug_info", unit_offset)?;
+
+        let program = match unit.line_program.clone() {
+            Some(program) => program,
+            None => continue,
         };
-        let comp_dir = root
-            .attr(gimli::DW_AT_comp_dir)
-            .unwrap()
-            .and_then(|attr| attr.string_value(debug_str));
-        let comp_name = root
-            .attr(gimli::DW_AT_name)
-            .unwrap()
-            .and_then(|attr| attr.string_value(debug_str));
-        let program = debug_line.program(offset, unit.address_size(), comp_dir, comp_name);
-        let mut block_start_loc = locations.len();
-        if let Ok(program) = program {
-            let mut rows = program.rows();
-            while let Some((header, row)) = rows.next_row().unwrap() {
-                let pc = debug_sections.code_start as u64 + row.address();
-                // let pc = row.address();
-                let line = row.line().unwrap_or(0);
-                let column = match row.column() {
-                    gimli::ColumnType::Column(column) => column,
-                    gimli::ColumnType::LeftEdge => 0,
-                };
-                let file_index = row.file_index();
-                let source_id = if !source_to_id_map.contains_key(&file_index) {
-                    let file_path: String = if let Some(file) = row.file(header) {
-                        if let Some(directory) = file.directory(header) {
-                            format!(
-                                "{}/{}",
-                                directory.to_string_lossy(),
-                                file.path_name().to_string_lossy()
-                            )
-                        } else {
-                            String::from(file.path_name().to_string_lossy())
-                        }
-                    } else {
-                        String::from("<unknown>")
-                    };
-                    let index = sources.len();
-                    sources.push(file_path);
-                    source_to_id_map.insert(file_index, index);
-                    index
-                } else {
-                    *source_to_id_map.get(&file_index).unwrap() as usize
-                };
-                let loc = DebugLoc {
-                    address: pc,
-                    source_id: source_id as u32,
-                    line: line as u32,
-                    column: column as u32,
-                };
-                locations.push(loc);
-                if row.end_sequence() {
-                    // Heuristic to remove dead functions.
-                    let block_end_loc = locations.len() - 1;
-                    let fn_size =
-                        locations[block_end_loc].address - locations[block_start_loc].address + 1;
-                    let fn_size_field_len =
-                        ((fn_size + 1).next_power_of_two().trailing_zeros() + 6) / 7;
-                    if locations[block_start_loc].address
-                        <= debug_sections.code_start as u64 + fn_size_field_len as u64
-                    {
-                        locations.drain(block_start_loc..);
+        let line_header = program.header();
+        let line_offset = line_header.offset().0;
+        // Tombstone for the address size of the unit, 8 bytes in wasm64.
+        let tombstone = !0u64 >> (64 - unit.encoding().address_size as u32 * 8);
+
+        let mut entries = unit.entries();
+        let mut depth = 0;
+        // Tree depths of the enclosing inlined instances.
+        let mut inline_depths: Vec<isize> = Vec::new();
+        while let Some((delta_depth, entry)) =
+            entries.next_dfs().in_section(".debug_info", unit_offset)?
+        {
+            depth += delta_depth;
+            while inline_depths.last().is_some_and(|d| *d >= depth) {
+                inline_depths.pop();
+            }
+            let tag = entry.tag();
+            if tag != gimli::DW_TAG_subprogram && tag != gimli::DW_TAG_inlined_subroutine {
+                continue;
+            }
+            let name_id = die_name(&dwarf, &unit, entry)
+                .in_section(".debug_info", unit_offset)?
+                .map(|name| {
+                    *name_to_id_map.entry(name).or_insert_with_key(|name| {
+                        names.push(name.clone());
+                        names.len() as u32 - 1
+                    })
+                });
+            let mut ranges = dwarf
+                .die_ranges(&unit, entry)
+                .in_section(".debug_info", unit_offset)?;
+            let mut entry_ranges = Vec::new();
+            while let Some(range) = ranges.next().in_section(".debug_info", unit_offset)? {
+                // Functions removed by the linker are moved to 0 or -1.
+                if range.begin == 0 || range.begin >= tombstone - 1 {
+                    continue;
+                }
+                entry_ranges.push((
+                    debug_sections.code_start as u64 + range.begin,
+                    debug_sections.code_start as u64 + range.end,
+                ));
+            }
+            if tag == gimli::DW_TAG_subprogram {
+                if let Some(name_id) = name_id {
+                    for (begin, end) in entry_ranges {
+                        functions.push(FunctionRange {
+                            begin,
+                            end,
+                            name_id,
+                        });
                     }
-                    block_start_loc = locations.len();
                 }
+                continue;
             }
+
+            let call_source_id = match entry
+                .attr_value(gimli::DW_AT_call_file)
+                .in_section(".debug_info", unit_offset)?
+            {
+                Some(gimli::AttributeValue::FileIndex(file_index)) => Some(
+                    source_id(
+                        &dwarf,
+                        &unit,
+                        line_header,
+                        file_index,
+                        &mut sources,
+                        &mut path_to_id_map,
+                        &mut source_to_id_map,
+                    )
+                    .in_section(".debug_line", line_offset)? as u32,
+                ),
+                _ => None,
+            };
+            let udata = |attr| -> Result<u32> {
+                Ok(entry
+                    .attr_value(attr)
+                    .in_section(".debug_info", unit_offset)?
+                    .and_then(|value| value.udata_value())
+                    .unwrap_or(0) as u32)
+            };
+            let call_line = udata(gimli::DW_AT_call_line)?;
+            let call_column = udata(gimli::DW_AT_call_column)?;
+            for (begin, end) in entry_ranges {
+                inlined_calls.push(InlinedCall {
+                    begin,
+                    end,
+                    name_id,
+                    call_source_id,
+                    call_line,
+                    call_column,
+                    depth: inline_depths.len() as u32,
+                });
+            }
+            inline_depths.push(depth);
         }
 
-        // new unit, new sources
+        let mut block_start_loc = locations.len();
+        let mut rows = program.rows();
+        while let Some((header, row)) = rows.next_row().in_section(".debug_line", line_offset)? {
+            let pc = debug_sections.code_start as u64 + row.address();
+            // let pc = row.address();
+            let line = row.line().map_or(0, |line| line.get());
+            let column = match row.column() {
+                gimli::ColumnType::Column(column) => column.get(),
+                gimli::ColumnType::LeftEdge => 0,
+            };
+            let source_id = source_id(
+                &dwarf,
+                &unit,
+                header,
+                row.file_index(),
+                &mut sources,
+                &mut path_to_id_map,
+                &mut source_to_id_map,
+            )
+            .in_section(".debug_line", line_offset)?;
+            let loc = DebugLoc {
+                address: pc,
+                source_id: source_id as u32,
+                line: line as u32,
+                column: column as u32,
+                name_id: None,
+                end_sequence: row.end_sequence(),
+            };
+            locations.push(loc);
+            if row.end_sequence() {
+                // Heuristic to remove dead functions.
+                let block_end_loc = locations.len() - 1;
+                let fn_size =
+                    locations[block_end_loc].address - locations[block_start_loc].address + 1;
+                let fn_size_field_len = (fn_size + 1)
+                    .next_power_of_two()
+                    .trailing_zeros()
+                    .div_ceil(7);
+                if locations[block_start_loc].address
+                    <= debug_sections.code_start as u64 + fn_size_field_len as u64
+                {
+                    locations.drain(block_start_loc..);
+                }
+                block_start_loc = locations.len();
+            }
+        }
+
+        // File indices are local to the line program of the unit.
         source_to_id_map.clear();
     }
 
-    locations.sort_by(|a, b| a.address.cmp(&b.address));
+    locations.sort_by_key(|loc| (loc.address, !loc.end_sequence));
+
+    functions.sort_by_key(|function| function.begin);
+    inlined_calls.sort_by_key(|call| call.begin);
+    // Rows of inlined code are named after the innermost inlined function.
+    let mut inline_stacks = InlineStacks::new(&inlined_calls);
+    for loc in locations.iter_mut() {
+        let next = functions.partition_point(|function| function.begin <= loc.address);
+        if next > 0 && loc.address < functions[next - 1].end {
+            loc.name_id = Some(functions[next - 1].name_id);
+        }
+        if let Some(call) = inline_stacks
+            .at(loc.address)
+            .into_iter()
+            .find(|call| call.name_id.is_some())
+        {
+            loc.name_id = call.name_id;
+        }
+    }
 
-    DebugLocInfo { sources, locations }
+    Ok(DebugLocInfo {
+        sources,
+        names,
+        locations,
+        inlined_calls,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::join_paths;
+
+    #[test]
+    fn join_relative_paths() {
+        assert_eq!(join_paths(&["build", "./src", "a.c"]), "build/src/a.c");
+        assert_eq!(join_paths(&["build", "../a.c"]), "a.c");
+        assert_eq!(join_paths(&["..", "../a.c"]), "../../a.c");
+        assert_eq!(join_paths(&["build", ".."]), ".");
+    }
+
+    #[test]
+    fn join_absolute_paths() {
+        assert_eq!(
+            join_paths(&["/build", "/usr/include", "stdio.h"]),
+            "/usr/include/stdio.h"
+        );
+        assert_eq!(join_paths(&["/build", "../../a.c"]), "/a.c");
+        assert_eq!(join_paths(&["/", ".."]), "/");
+    }
+
+    #[test]
+    fn join_drive_paths() {
+        assert_eq!(join_paths(&["C:\\src", "lib\\a.c"]), "C:/src/lib/a.c");
+        assert_eq!(join_paths(&["C:\\src", "..\\..\\a.c"]), "C:/a.c");
+        assert_eq!(join_paths(&["/build", "d:/src", "a.c"]), "d:/src/a.c");
+        assert_eq!(
+            join_paths(&["C:\\src\\.\\a.c"]),
+            join_paths(&["C:/src/a.c"])
+        );
+    }
+
+    #[test]
+    fn join_unc_paths() {
+        assert_eq!(
+            join_paths(&["\\\\server\\share", "src\\a.c"]),
+            "//server/share/src/a.c"
+        );
+        assert_eq!(
+            join_paths(&["/build", "\\\\server\\share\\a.c"]),
+            "//server/share/a.c"
+        );
+    }
 }
@@ -0,0 +1,159 @@
+// Errors reported by the library.
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::result;
+
+use gimli;
+use linking::SUPPORTED_VERSIONS;
+use wasmparser::BinaryReaderError;
+
+/// Name used in errors for problems outside of any particular section.
+pub const MODULE: &str = "<module>";
+
+/// Errors produced while reading a wasm module or its debug information.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// Reading or writing the named file failed.
+    File(String, io::Error),
+    /// A section of the module is malformed or unsupported. The offset is
+    /// relative to the section payload, or to the start of the module for
+    /// `MODULE`.
+    InvalidSection {
+        section: String,
+        offset: usize,
+        cause: Cause,
+    },
+    /// A section needed for the operation is not present in the module.
+    MissingSection(String),
+    /// A source file was not found under any of the source roots.
+    SourceNotFound(String),
+}
+
+/// The reason a section was rejected.
+#[derive(Debug)]
+pub enum Cause {
+    /// The wasm binary encoding is malformed.
+    Wasm(BinaryReaderError),
+    /// The file does not start with the magic number and version 1 of a
+    /// wasm module.
+    InvalidHeader,
+    /// The DWARF data is malformed.
+    Dwarf(gimli::Error),
+    /// The linking section has an unsupported version.
+    UnsupportedVersion(u32),
+    /// The linking section uses an unknown symbol kind.
+    UnknownSymbolKind(u32),
+    /// A COMDAT group of the linking section has a member of unknown kind.
+    UnknownComdatKind(u32),
+    /// A relocation entry has an unknown type.
+    UnknownRelocType(u32),
+    /// A relocation refers to a symbol that is not defined, or of the wrong kind.
+    InvalidSymbol(u32),
+    /// A symbol refers to a data segment, function or section that does not exist.
+    InvalidIndex(u32),
+    /// A relocation patches bytes outside of its target section.
+    OutOfBounds(usize),
+    /// A data segment offset is not a constant expression.
+    UnsupportedInitExpr,
+    /// A relocation refers to a data segment whose offset depends on an
+    /// imported global that was not given.
+    UnknownSegmentOffset(u32),
+}
+
+/// Result type used across the library.
+pub type Result<T> = result::Result<T, Error>;
+
+impl Error {
+    pub fn invalid(section: &str, offset: usize, cause: Cause) -> Error {
+        Error::InvalidSection {
+            section: section.to_owned(),
+            offset,
+            cause,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::File(path, err) => write!(f, "{}: {}", path, err),
+            Error::InvalidSection {
+                section,
+                offset,
+                cause,
+            } => write!(f, "{} at offset {:#x}: {}", section, offset, cause),
+            Error::MissingSection(section) => write!(f, "section {} was not found", section),
+            Error::SourceNotFound(path) => write!(f, "source file {} not found", path),
+        }
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Cause::Wasm(err) => write!(f, "{}", err.message()),
+            Cause::Dwarf(err) => write!(f, "invalid DWARF data: {}", err),
+            Cause::InvalidHeader => write!(f, "not a wasm module"),
+            Cause::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported version {}, expected one of {:?}",
+                version, SUPPORTED_VERSIONS
+            ),
+            Cause::UnknownSymbolKind(kind) => write!(f, "unknown symbol kind {}", kind),
+            Cause::UnknownComdatKind(kind) => write!(f, "unknown COMDAT member kind {}", kind),
+            Cause::UnknownRelocType(ty) => write!(f, "unknown relocation type {}", ty),
+            Cause::InvalidSymbol(index) => write!(f, "unexpected symbol {}", index),
+            Cause::InvalidIndex(index) => write!(f, "index {} is out of range", index),
+            Cause::OutOfBounds(offset) => {
+                write!(f, "relocation at {:#x} is out of bounds", offset)
+            }
+            Cause::UnsupportedInitExpr => write!(f, "unsupported init expression"),
+            Cause::UnknownSegmentOffset(segment) => {
+                write!(f, "offset of data segment {} is not known", segment)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) | Error::File(_, err) => Some(err),
+            Error::InvalidSection {
+                cause: Cause::Wasm(err),
+                ..
+            } => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+/// Attributes lower level errors to a section of the module.
+pub trait ResultExt<T> {
+    fn in_section(self, section: &str, offset: usize) -> Result<T>;
+}
+
+// Reader errors carry their own offset, so `offset` is the position of the
+// reader's data within the section.
+impl<T> ResultExt<T> for result::Result<T, BinaryReaderError> {
+    fn in_section(self, section: &str, offset: usize) -> Result<T> {
+        self.map_err(|err| Error::invalid(section, offset + err.offset(), Cause::Wasm(err)))
+    }
+}
+
+impl<T> ResultExt<T> for result::Result<T, gimli::Error> {
+    fn in_section(self, section: &str, offset: usize) -> Result<T> {
+        self.map_err(|err| Error::invalid(section, offset, Cause::Dwarf(err)))
+    }
+}
@@ -0,0 +1,55 @@
+//! Reads DWARF debug information from wasm files and converts it to
+//! source maps.
+//!
+//! ```no_run
+//! # fn run(wasm: &[u8]) -> wasm_dwarf::Result<()> {
+//! let mut debug_sections = wasm_dwarf::DebugSections::read_sections(wasm)?;
+//! if debug_sections.linking_section.is_some() {
+//!     wasm_dwarf::reloc(&mut debug_sections)?;
+//! }
+//! let di = wasm_dwarf::get_debug_loc(&debug_sections)?;
+//! let map = wasm_dwarf::convert_debug_info_to_json(&di, &di.sources, None);
+//! println!("{}", map);
+//! # Ok(())
+//! # }
+//! ```
+
+extern crate gimli;
+extern crate rustc_serialize;
+extern crate vlq;
+extern crate wasmparser;
+
+pub mod dwarf;
+pub mod error;
+pub mod linking;
+pub mod map_source;
+pub mod reloc;
+pub mod stack_trace;
+pub mod to_json;
+pub mod wasm_read;
+
+pub use dwarf::{get_debug_loc, DebugLoc, DebugLocInfo, InlineStacks, InlinedCall};
+pub use error::{Cause, Error, Result};
+pub use linking::{read_linking, Linking};
+pub use map_source::{map_source, Callee, ListingMode, SourceMapEntry};
+pub use reloc::reloc;
+pub use stack_trace::symbolize_stack_trace_line;
+pub use to_json::convert_debug_info_to_json;
+pub use wasm_read::{
+    add_source_mapping_url_section, write_with_source_mapping_url, DebugSections, Names,
+    SegmentOffset,
+};
+
+use rustc_serialize::json::Json;
+
+/// Reads the wasm module and converts its DWARF line information to a
+/// Source Map v3 document, relocating the debug sections first when
+/// `relocate` is set. Source paths are written as found in the DWARF data.
+pub fn wasm_to_source_map(wasm: &[u8], relocate: bool) -> Result<Json> {
+    let mut debug_sections = DebugSections::read_sections(wasm)?;
+    if relocate {
+        reloc(&mut debug_sections)?;
+    }
+    let di = get_debug_loc(&debug_sections)?;
+    Ok(convert_debug_info_to_json(&di, &di.sources, None))
+}
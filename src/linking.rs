@@ -0,0 +1,345 @@
+// Decodes the `linking` custom section of object files.
+
+use std::fmt;
+
+use wasmparser::{BinaryReader, WasmFeatures};
+
+use error::{Cause, Error, Result, ResultExt};
+
+/// Versions of the linking section that `read_linking` decodes.
+pub const SUPPORTED_VERSIONS: &[u32] = &[1, 2];
+
+const WASM_SEGMENT_INFO: u32 = 5;
+const WASM_INIT_FUNCS: u32 = 6;
+const WASM_COMDAT_INFO: u32 = 7;
+const WASM_SYMBOL_TABLE: u32 = 8;
+
+pub const WASM_SYM_BINDING_WEAK: u32 = 0x1;
+pub const WASM_SYM_BINDING_LOCAL: u32 = 0x2;
+pub const WASM_SYM_VISIBILITY_HIDDEN: u32 = 0x4;
+pub const WASM_SYM_UNDEFINED: u32 = 0x10;
+pub const WASM_SYM_EXPORTED: u32 = 0x20;
+pub const WASM_SYM_EXPLICIT_NAME: u32 = 0x40;
+pub const WASM_SYM_NO_STRIP: u32 = 0x80;
+pub const WASM_SYM_TLS: u32 = 0x100;
+pub const WASM_SYM_ABSOLUTE: u32 = 0x200;
+
+const SYMBOL_FLAG_NAMES: &[(u32, &str)] = &[
+    (WASM_SYM_BINDING_WEAK, "weak"),
+    (WASM_SYM_BINDING_LOCAL, "local"),
+    (WASM_SYM_VISIBILITY_HIDDEN, "hidden"),
+    (WASM_SYM_UNDEFINED, "undefined"),
+    (WASM_SYM_EXPORTED, "exported"),
+    (WASM_SYM_EXPLICIT_NAME, "explicit_name"),
+    (WASM_SYM_NO_STRIP, "no_strip"),
+    (WASM_SYM_TLS, "tls"),
+    (WASM_SYM_ABSOLUTE, "absolute"),
+];
+
+pub const WASM_SEG_FLAG_STRINGS: u32 = 0x1;
+pub const WASM_SEG_FLAG_TLS: u32 = 0x2;
+pub const WASM_SEG_FLAG_RETAIN: u32 = 0x4;
+
+const SEGMENT_FLAG_NAMES: &[(u32, &str)] = &[
+    (WASM_SEG_FLAG_STRINGS, "strings"),
+    (WASM_SEG_FLAG_TLS, "tls"),
+    (WASM_SEG_FLAG_RETAIN, "retain"),
+];
+
+/// Contents of the `linking` section.
+pub struct Linking {
+    pub version: u32,
+    /// Data segments, from `WASM_SEGMENT_INFO`.
+    pub segments: Vec<SegmentInfo>,
+    /// Initialization functions, from `WASM_INIT_FUNCS`.
+    pub init_funcs: Vec<InitFunc>,
+    /// COMDAT groups, from `WASM_COMDAT_INFO`.
+    pub comdats: Vec<Comdat>,
+    /// Symbols, from `WASM_SYMBOL_TABLE`. Relocations refer to them by
+    /// their index.
+    pub symbols: Vec<Symbol>,
+}
+
+pub struct SegmentInfo {
+    pub name: String,
+    /// Alignment of the segment as a power of 2.
+    pub alignment: u32,
+    /// `WASM_SEG_FLAG_*` bits.
+    pub flags: u32,
+}
+
+pub struct InitFunc {
+    /// Functions with a lower priority run first.
+    pub priority: u32,
+    /// Index of the function symbol.
+    pub symbol: u32,
+}
+
+pub struct Comdat {
+    pub name: String,
+    pub flags: u32,
+    pub members: Vec<ComdatMember>,
+}
+
+/// An item of a COMDAT group, with its index in its index space (the data
+/// segment, function or section index, etc.).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComdatMember {
+    Data(u32),
+    Function(u32),
+    Global(u32),
+    Tag(u32),
+    Table(u32),
+    Section(u32),
+}
+
+pub struct Symbol {
+    /// `WASM_SYM_*` bits.
+    pub flags: u32,
+    /// Undefined symbols only have a name of their own with
+    /// `WASM_SYM_EXPLICIT_NAME`, otherwise they take the import name.
+    pub name: Option<String>,
+    pub kind: SymbolKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function(u32),
+    /// Undefined data symbols have no location.
+    Data(Option<DataLocation>),
+    Global(u32),
+    Section(u32),
+    Tag(u32),
+    Table(u32),
+}
+
+/// Where a defined data symbol lives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DataLocation {
+    pub segment: u32,
+    /// Offset of the symbol in its segment.
+    pub offset: u64,
+    pub size: u64,
+}
+
+impl Symbol {
+    pub fn is_defined(&self) -> bool {
+        (self.flags & WASM_SYM_UNDEFINED) == 0
+    }
+}
+
+// Formats the set bits as "[weak, hidden]", with unknown bits in hex.
+fn write_flags(f: &mut fmt::Formatter, flags: u32, names: &[(u32, &str)]) -> fmt::Result {
+    if flags == 0 {
+        return Ok(());
+    }
+    let mut parts: Vec<String> = names
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, name)| String::from(*name))
+        .collect();
+    let unknown = names.iter().fold(flags, |flags, (flag, _)| flags & !flag);
+    if unknown != 0 {
+        parts.push(format!("{:#x}", unknown));
+    }
+    write!(f, " [{}]", parts.join(", "))
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            SymbolKind::Function(index) => write!(f, "function ${}", index)?,
+            SymbolKind::Data(_) => write!(f, "data")?,
+            SymbolKind::Global(index) => write!(f, "global ${}", index)?,
+            SymbolKind::Section(index) => write!(f, "section {}", index)?,
+            SymbolKind::Tag(index) => write!(f, "tag ${}", index)?,
+            SymbolKind::Table(index) => write!(f, "table ${}", index)?,
+        }
+        if let Some(name) = &self.name {
+            write!(f, " {}", name)?;
+        }
+        if let SymbolKind::Data(Some(location)) = self.kind {
+            write!(
+                f,
+                " segment {} offset {:#x} size {}",
+                location.segment, location.offset, location.size
+            )?;
+        }
+        write_flags(f, self.flags, SYMBOL_FLAG_NAMES)
+    }
+}
+
+impl fmt::Display for SegmentInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} align {}", self.name, 1u64 << self.alignment.min(63))?;
+        write_flags(f, self.flags, SEGMENT_FLAG_NAMES)
+    }
+}
+
+impl fmt::Display for ComdatMember {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ComdatMember::Data(index) => write!(f, "data {}", index),
+            ComdatMember::Function(index) => write!(f, "function ${}", index),
+            ComdatMember::Global(index) => write!(f, "global ${}", index),
+            ComdatMember::Tag(index) => write!(f, "tag ${}", index),
+            ComdatMember::Table(index) => write!(f, "table ${}", index),
+            ComdatMember::Section(index) => write!(f, "section {}", index),
+        }
+    }
+}
+
+fn read_symbol(reader: &mut BinaryReader) -> Result<Symbol> {
+    let symbol_start = reader.original_position();
+    let kind = reader.read_var_u32().in_section("linking", 0)?;
+    let flags = reader.read_var_u32().in_section("linking", 0)?;
+    let defined = (flags & WASM_SYM_UNDEFINED) == 0;
+    let mut name = None;
+    let kind = match kind {
+        0x0 /* WASM_SYMBOL_TYPE_FUNCTION */
+        | 0x2 /* WASM_SYMBOL_TYPE_GLOBAL */
+        | 0x4 /* WASM_SYMBOL_TYPE_TAG */
+        | 0x5 /* WASM_SYMBOL_TYPE_TABLE */ => {
+            let index = reader.read_var_u32().in_section("linking", 0)?;
+            if defined || (flags & WASM_SYM_EXPLICIT_NAME) != 0 {
+                name = Some(reader.read_string().in_section("linking", 0)?.to_owned());
+            }
+            match kind {
+                0x0 => SymbolKind::Function(index),
+                0x2 => SymbolKind::Global(index),
+                0x4 => SymbolKind::Tag(index),
+                _ => SymbolKind::Table(index),
+            }
+        }
+        0x1 /* WASM_SYMBOL_TYPE_DATA */ => {
+            name = Some(reader.read_string().in_section("linking", 0)?.to_owned());
+            if defined {
+                let segment = reader.read_var_u32().in_section("linking", 0)?;
+                let offset = reader.read_var_u64().in_section("linking", 0)?;
+                let size = reader.read_var_u64().in_section("linking", 0)?;
+                SymbolKind::Data(Some(DataLocation {
+                    segment,
+                    offset,
+                    size,
+                }))
+            } else {
+                SymbolKind::Data(None)
+            }
+        }
+        0x3 /* WASM_SYMBOL_TYPE_SECTION */ => {
+            SymbolKind::Section(reader.read_var_u32().in_section("linking", 0)?)
+        }
+        _ => {
+            return Err(Error::invalid(
+                "linking",
+                symbol_start,
+                Cause::UnknownSymbolKind(kind),
+            ))
+        }
+    };
+    Ok(Symbol { flags, name, kind })
+}
+
+fn read_comdat(reader: &mut BinaryReader) -> Result<Comdat> {
+    let name = reader.read_string().in_section("linking", 0)?.to_owned();
+    let flags = reader.read_var_u32().in_section("linking", 0)?;
+    let count = reader.read_var_u32().in_section("linking", 0)?;
+    let mut members = Vec::new();
+    for _ in 0..count {
+        let member_start = reader.original_position();
+        let kind = reader.read_u8().in_section("linking", 0)? as u32;
+        let index = reader.read_var_u32().in_section("linking", 0)?;
+        members.push(match kind {
+            0x0 /* WASM_COMDAT_DATA */ => ComdatMember::Data(index),
+            0x1 /* WASM_COMDAT_FUNCTION */ => ComdatMember::Function(index),
+            0x2 /* WASM_COMDAT_GLOBAL */ => ComdatMember::Global(index),
+            0x3 /* WASM_COMDAT_TAG */ => ComdatMember::Tag(index),
+            0x4 /* WASM_COMDAT_TABLE */ => ComdatMember::Table(index),
+            0x5 /* WASM_COMDAT_SECTION */ => ComdatMember::Section(index),
+            _ => {
+                return Err(Error::invalid(
+                    "linking",
+                    member_start,
+                    Cause::UnknownComdatKind(kind),
+                ))
+            }
+        });
+    }
+    Ok(Comdat {
+        name,
+        flags,
+        members,
+    })
+}
+
+/// Decodes the payload of a `linking` section. Unknown subsections are
+/// skipped, unknown versions are rejected.
+///
+/// ```
+/// # fn run() -> wasm_dwarf::Result<()> {
+/// // A symbol table with a single function symbol `f`.
+/// for version in [1, 2] {
+///     let data = [version, 8, 6, 1, 0, 0, 0, 1, b'f'];
+///     let linking = wasm_dwarf::read_linking(&data)?;
+///     assert_eq!(linking.symbols[0].name.as_deref(), Some("f"));
+/// }
+/// assert!(wasm_dwarf::read_linking(&[3]).is_err());
+/// # Ok(())
+/// # }
+/// # run().unwrap();
+/// ```
+pub fn read_linking(data: &[u8]) -> Result<Linking> {
+    let mut reader = BinaryReader::new(data, 0, WasmFeatures::all());
+    let version = reader.read_var_u32().in_section("linking", 0)?;
+    // Both versions share the layout of the subsections read here.
+    if !SUPPORTED_VERSIONS.contains(&version) {
+        return Err(Error::invalid(
+            "linking",
+            0,
+            Cause::UnsupportedVersion(version),
+        ));
+    }
+    let mut linking = Linking {
+        version,
+        segments: Vec::new(),
+        init_funcs: Vec::new(),
+        comdats: Vec::new(),
+        symbols: Vec::new(),
+    };
+    while !reader.eof() {
+        let subsection_type = reader.read_u8().in_section("linking", 0)? as u32;
+        let subsection_len = reader.read_var_u32().in_section("linking", 0)? as usize;
+        let subsection_start = reader.original_position();
+        let subsection = reader.read_bytes(subsection_len).in_section("linking", 0)?;
+        let mut reader = BinaryReader::new(subsection, subsection_start, WasmFeatures::all());
+        if !matches!(
+            subsection_type,
+            WASM_SEGMENT_INFO | WASM_INIT_FUNCS | WASM_COMDAT_INFO | WASM_SYMBOL_TABLE
+        ) {
+            continue;
+        }
+        let count = reader.read_var_u32().in_section("linking", 0)?;
+        for _ in 0..count {
+            match subsection_type {
+                WASM_SEGMENT_INFO => {
+                    let name = reader.read_string().in_section("linking", 0)?.to_owned();
+                    let alignment = reader.read_var_u32().in_section("linking", 0)?;
+                    let flags = reader.read_var_u32().in_section("linking", 0)?;
+                    linking.segments.push(SegmentInfo {
+                        name,
+                        alignment,
+                        flags,
+                    });
+                }
+                WASM_INIT_FUNCS => {
+                    let priority = reader.read_var_u32().in_section("linking", 0)?;
+                    let symbol = reader.read_var_u32().in_section("linking", 0)?;
+                    linking.init_funcs.push(InitFunc { priority, symbol });
+                }
+                WASM_COMDAT_INFO => linking.comdats.push(read_comdat(&mut reader)?),
+                _ => linking.symbols.push(read_symbol(&mut reader)?),
+            }
+        }
+    }
+    Ok(linking)
+}
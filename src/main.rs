@@ -1,32 +1,24 @@
-use std::fmt::Error;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
-use std::{env, io::BufReader};
+use std::{env, io, io::BufReader, process};
 
-use dwarf::get_debug_loc;
-use getopts::Options;
-use map_source::{map_source, SourceMapEntry};
-use reloc::reloc;
+use getopts::{Matches, Options};
 use std::path::Path;
-use wasm_read::{add_source_mapping_url_section, DebugSections};
+use wasm_dwarf::{
+    convert_debug_info_to_json, get_debug_loc, map_source, reloc, symbolize_stack_trace_line,
+    write_with_source_mapping_url, DebugSections, Error, ListingMode, Result, SourceMapEntry,
+};
 
 extern crate getopts;
-extern crate gimli;
-extern crate rustc_serialize;
-extern crate vlq;
-extern crate wasmparser;
-
-mod dwarf;
-mod map_source;
-mod reloc;
-mod wasm_read;
+extern crate wasm_dwarf;
 
 struct PrefixReplacements {
     replacements: Vec<(String, String)>,
 }
 
 impl PrefixReplacements {
-    fn parse(input: &Vec<String>) -> PrefixReplacements {
+    fn parse(input: &[String]) -> PrefixReplacements {
         let mut replacements = Vec::new();
         for i in input.iter() {
             let separator = i.find('=');
@@ -42,11 +34,11 @@ impl PrefixReplacements {
                 replacements.push((i.clone(), String::new()))
             }
         }
-        return PrefixReplacements { replacements };
+        PrefixReplacements { replacements }
     }
 
-    fn replace(&self, path: &String) -> String {
-        let mut result = path.clone();
+    fn replace(&self, path: &str) -> String {
+        let mut result = path.to_owned();
         for (ref old_prefix, ref new_prefix) in self.replacements.iter() {
             if path.starts_with(old_prefix) {
                 result = result.split_off(old_prefix.len());
@@ -57,9 +49,9 @@ impl PrefixReplacements {
         result
     }
 
-    fn replace_all(&self, paths: &mut Vec<String>) {
+    fn replace_all(&self, paths: &mut [String]) {
         for path in paths.iter_mut() {
-            *path = self.replace(&path);
+            *path = self.replace(path);
         }
     }
 }
@@ -69,110 +61,399 @@ fn main() {
     opts.optopt("o", "", "set output file name", "NAME");
     opts.optflag("", "relocation", "perform relocation first");
     opts.optflag("l", "list-source", "list source files");
+    opts.optflag(
+        "c",
+        "call-sites",
+        "write the call sites listing instead of the source map",
+    );
+    opts.optopt(
+        "i",
+        "instructions",
+        "instructions in the listing: calls (default), lines or all",
+        "KIND",
+    );
     opts.optopt(
         "m",
         "source-map",
         "specifies sourceMappingURL section contest",
         "URL",
     );
+    opts.optopt(
+        "w",
+        "",
+        "write the input wasm with the sourceMappingURL section",
+        "NAME",
+    );
     opts.optmulti(
         "s",
         "source-roots",
         "Search source files under these roots if they are not found under current directory.",
         "DIR",
     );
+    opts.optflag(
+        "e",
+        "embed-sources",
+        "embed the source files contents into the source map",
+    );
+    opts.optmulti(
+        "p",
+        "prefix",
+        "Replace source filename prefix in the source map.",
+        "OLD=NEW",
+    );
+    opts.optmulti(
+        "g",
+        "global",
+        "value of an imported global used by data segment offsets",
+        "NAME=VALUE",
+    );
     opts.optflag("h", "help", "print this help menu");
 
     let args: Vec<_> = env::args().collect();
     let program = args[0].clone();
     let args = match opts.parse(&args[1..]) {
         Ok(m) => m,
-        Err(f) => panic!(f.to_string()),
+        Err(f) => {
+            eprintln!("{}: {}", program, f);
+            process::exit(2);
+        }
     };
+    let command = args.free.first().map(String::as_str);
+    let subcommand = matches!(
+        command,
+        Some("symbolize") | Some("trace") | Some("locate") | Some("linking")
+    );
     if args.opt_present("h")
-        || args.free.len() < 1
-        || !(args.opt_present("o") || args.opt_present("l"))
+        || args.free.is_empty()
+        || (subcommand && args.free.len() < 2)
+        || (command == Some("locate") && args.free.len() < 3)
+        || !(subcommand || args.opt_present("o") || args.opt_present("l") || args.opt_present("w"))
+        || (args.opt_present("w") && !(args.opt_present("m") || args.opt_present("o")))
     {
         return print_usage(&program, opts);
     }
 
-    let perform_reloc = args.opt_present("relocation");
-    let filename = args.free[0].clone();
-    let mut f = File::open(filename).expect("file not found");
+    let mode = match args.opt_str("i").as_deref() {
+        None | Some("calls") => ListingMode::Calls,
+        Some("lines") => ListingMode::Lines,
+        Some("all") => ListingMode::Instructions,
+        Some(kind) => {
+            eprintln!("{}: unknown instructions kind {}", program, kind);
+            process::exit(2);
+        }
+    };
+
+    let result = match command {
+        Some("symbolize") => run_symbolize(&args),
+        Some("trace") => run_trace(&args),
+        Some("locate") => run_locate(&args),
+        Some("linking") => run_linking(&args),
+        _ => run(&args, mode),
+    };
+    if let Err(err) = result {
+        eprintln!("{}: {}", program, err);
+        process::exit(1);
+    }
+}
+
+fn read_file(filename: &str) -> Result<Vec<u8>> {
     let mut data = Vec::new();
-    f.read_to_end(&mut data).expect("unable to read file");
+    File::open(filename)
+        .and_then(|mut f| f.read_to_end(&mut data))
+        .map_err(|err| Error::File(filename.to_owned(), err))?;
+    Ok(data)
+}
+
+// Reads the debug sections with the global values given by -g, and
+// applies the relocations if requested.
+fn read_debug_sections<'a>(args: &Matches, data: &'a [u8]) -> Result<DebugSections<'a>> {
+    let mut global_values = HashMap::new();
+    for global in args.opt_strs("g") {
+        match global
+            .split_once('=')
+            .and_then(|(name, value)| Some((name, parse_offset(value)?)))
+        {
+            Some((name, value)) => {
+                global_values.insert(name.to_owned(), value);
+            }
+            None => eprintln!("warning: invalid global value {}", global),
+        }
+    }
+    let mut debug_sections = DebugSections::read_sections_with_globals(data, &global_values)?;
+    if args.opt_present("relocation") {
+        reloc(&mut debug_sections)?;
+    }
+    Ok(debug_sections)
+}
+
+fn parse_offset(s: &str) -> Option<u64> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+// Prints the contents of the linking section.
+fn run_linking(args: &Matches) -> Result<()> {
+    let data = read_file(&args.free[1])?;
+    let debug_sections = DebugSections::read_sections(data.as_slice())?;
+    let linking = debug_sections
+        .linking()?
+        .ok_or_else(|| Error::MissingSection(String::from("linking")))?;
+
+    println!("version {}", linking.version);
+    for (index, segment) in linking.segments.iter().enumerate() {
+        println!("segment {} {}", index, segment);
+    }
+    for init_func in linking.init_funcs.iter() {
+        println!(
+            "init priority {} symbol {}",
+            init_func.priority, init_func.symbol
+        );
+    }
+    for comdat in linking.comdats.iter() {
+        println!("comdat {} flags {:#x}", comdat.name, comdat.flags);
+        for member in comdat.members.iter() {
+            println!("\t{}", member);
+        }
+    }
+    for (index, symbol) in linking.symbols.iter().enumerate() {
+        println!("symbol {} {}", index, symbol);
+    }
+    Ok(())
+}
+
+// Splits `file:line[:column]`. The file name may itself contain colons.
+fn parse_source_position(s: &str) -> Option<(&str, u32, Option<u32>)> {
+    let (rest, last) = s.rsplit_once(':')?;
+    let last = last.parse().ok()?;
+    if let Some((file, line)) = rest.rsplit_once(':') {
+        if let Ok(line) = line.parse() {
+            return Some((file, line, Some(last)));
+        }
+    }
+    Some((rest, last, None))
+}
+
+// Prints the code ranges generated for every source position given on the
+// command line.
+fn run_locate(args: &Matches) -> Result<()> {
+    let data = read_file(&args.free[1])?;
+    let debug_sections = read_debug_sections(args, data.as_slice())?;
+    let di = get_debug_loc(&debug_sections)?;
+
+    for position in args.free[2..].iter() {
+        let (file, line, column) = match parse_source_position(position) {
+            Some(position) => position,
+            None => {
+                eprintln!("warning: invalid source position {}", position);
+                continue;
+            }
+        };
+        let ranges = di.address_ranges(file, line, column);
+        if ranges.is_empty() {
+            println!("{}\t??", position);
+        }
+        for range in ranges {
+            println!("{}\t{:#x}-{:#x}", position, range.start, range.end);
+        }
+    }
+    Ok(())
+}
+
+// Rewrites the wasm frames of the stack trace read from stdin.
+fn run_trace(args: &Matches) -> Result<()> {
+    let data = read_file(&args.free[1])?;
+    let debug_sections = read_debug_sections(args, data.as_slice())?;
+    let di = get_debug_loc(&debug_sections)?;
+
+    for line in io::stdin().lock().lines() {
+        println!("{}", symbolize_stack_trace_line(&line?, &di));
+    }
+    Ok(())
+}
 
-    let mut debug_sections = DebugSections::read_sections(data.as_slice());
+// Prints the function and source position of every code offset given on
+// the command line, or read from stdin.
+fn run_symbolize(args: &Matches) -> Result<()> {
+    let data = read_file(&args.free[1])?;
+    let debug_sections = read_debug_sections(args, data.as_slice())?;
+    let di = get_debug_loc(&debug_sections)?;
 
-    if perform_reloc {
-        if debug_sections.linking.is_none() {
-            panic!("relocation information was not found");
+    let mut offsets: Vec<String> = args.free[2..].to_vec();
+    if offsets.is_empty() {
+        for line in io::stdin().lock().lines() {
+            offsets.extend(line?.split_whitespace().map(String::from));
+        }
+    }
+    for offset in offsets.iter() {
+        let loc = match parse_offset(offset) {
+            Some(address) => di.lookup(address),
+            None => {
+                eprintln!("warning: invalid offset {}", offset);
+                continue;
+            }
+        };
+        match loc {
+            Some(loc) => println!(
+                "{}\t{}\t{}:{}:{}",
+                offset,
+                loc.name_id
+                    .map_or("??", |id| di.names[id as usize].as_str()),
+                di.sources[loc.source_id as usize],
+                loc.line,
+                loc.column
+            ),
+            None => println!("{}\t??", offset),
         }
-        reloc(&mut debug_sections);
     }
-    let mut di = get_debug_loc(&debug_sections);
-    // for debug_info in di.locations.iter() {
-    //     println!(
-    //         "{} {} {} {}",
-    //         debug_info.address, debug_info.source_id, debug_info.line, debug_info.column
-    //     );
-    // }
+    Ok(())
+}
+
+fn run(args: &Matches, mode: ListingMode) -> Result<()> {
+    let data = read_file(&args.free[0])?;
+
+    if let Some(wasm_output) = args.opt_str("w") {
+        // The map is expected next to the wasm file unless -m says otherwise.
+        let url = args.opt_str("m").or(args.opt_str("o")).unwrap();
+        let mut f_out =
+            File::create(&wasm_output).map_err(|err| Error::File(wasm_output.clone(), err))?;
+        write_with_source_mapping_url(data.as_slice(), &url, &mut f_out)?;
+        if !(args.opt_present("o") || args.opt_present("l")) {
+            return Ok(());
+        }
+    }
+
+    let debug_sections = read_debug_sections(args, data.as_slice())?;
+    let mut di = get_debug_loc(&debug_sections)?;
+    if di.locations.is_empty() {
+        let names = debug_sections.debug_section_names();
+        eprintln!(
+            "warning: no DWARF line information found (DWARF sections: {})",
+            if names.is_empty() {
+                String::from("none")
+            } else {
+                names.join(", ")
+            }
+        );
+    }
+
     let mut source_roots = args.opt_strs("source-roots");
     source_roots.insert(0, String::from("."));
-    let _sources = di.sources;
-    di.sources = Vec::new();
-    for file in _sources.iter() {
-        let mut source = Err(format!("source file {} not found", file));
+    let mut resolved_sources = Vec::new();
+    for file in di.sources.iter() {
+        let mut source = Err(Error::SourceNotFound(file.clone()));
         for source_root in source_roots.iter() {
             let path = Path::new(source_root).join(file);
             if path.as_path().exists() {
-                source = Ok(path.to_str().unwrap().to_owned());
+                source = Ok(path.to_string_lossy().into_owned());
                 break;
             }
         }
-        di.sources.push(source.expect(""));
+        resolved_sources.push(source);
     }
 
     if args.opt_present("l") {
-        for file in di.sources.iter() {
-            println!("{}", file);
+        for source in resolved_sources {
+            println!("{}", source?);
         }
-        return;
+        return Ok(());
     }
-    let mut sources_content = Vec::new();
-    for file in di.sources.iter() {
-        let f = File::open(file).expect("file not found");
-        let f = BufReader::new(f);
-        sources_content.push(f.lines().filter_map(|line| line.ok()).collect());
-    }
-
-    let source_map = map_source(data.as_slice(), &di, &sources_content);
 
     let output = args.opt_str("o").unwrap();
-    let mut result = String::new();
-    for (id, path) in di.sources.iter().enumerate() {
-        result += &format!("source {} {}\n", id, path);
-    }
-    for entry in source_map.iter() {
-        let SourceMapEntry {
-            address,
-            op,
-            source_file,
-            line,
-            source_code,
-        } = entry;
-
-        result += &format!(
-            "{}@{}\t{}\t({}:{})\n",
-            op, address, source_code, source_file, line
-        )
-    }
-    let mut f_out = File::create(output).expect("file cannot be created");
-    f_out.write(result.as_bytes()).expect("data written");
+    let result = if args.opt_present("c") || args.opt_present("i") {
+        di.sources = resolved_sources.into_iter().collect::<Result<_>>()?;
+        let mut sources_content = Vec::new();
+        for file in di.sources.iter() {
+            let f = File::open(file).map_err(|err| Error::File(file.clone(), err))?;
+            let f = BufReader::new(f);
+            sources_content.push(f.lines().map_while(|line| line.ok()).collect());
+        }
+
+        let names = debug_sections.names()?;
+        let source_map = map_source(
+            data.as_slice(),
+            &di,
+            &sources_content,
+            &names.functions,
+            mode,
+        )?;
+
+        let mut result = String::new();
+        for (id, path) in di.sources.iter().enumerate() {
+            result += &format!("source {} {}\n", id, path);
+        }
+        for entry in source_map.iter() {
+            let SourceMapEntry {
+                address,
+                op,
+                callee,
+                inline_stack,
+                source_file,
+                line,
+                column,
+                source_code,
+            } = entry;
+
+            result += &format!(
+                "{}@{}\t{}\t({}:{}:{})",
+                op, address, source_code, source_file, line, column
+            );
+            if let Some(callee) = callee {
+                result += &format!("\t-> {}", callee);
+            }
+            for call in inline_stack {
+                let name = call
+                    .name_id
+                    .map_or("<unknown>", |id| di.names[id as usize].as_str());
+                let file = call
+                    .call_source_id
+                    .map_or("<unknown>", |id| di.sources[id as usize].as_str());
+                result += &format!(
+                    "\t(inlined from {} at {}:{}:{})",
+                    name, file, call.call_line, call.call_column
+                );
+            }
+            result += "\n";
+        }
+        result
+    } else {
+        let sources_content: Option<Vec<Option<String>>> = if args.opt_present("e") {
+            Some(
+                resolved_sources
+                    .iter()
+                    .map(|source| {
+                        let file = source.as_ref().ok()?;
+                        let mut content = String::new();
+                        File::open(file)
+                            .and_then(|mut f| f.read_to_string(&mut content))
+                            .ok()?;
+                        Some(content)
+                    })
+                    .collect(),
+            )
+        } else {
+            None
+        };
+        // The map names sources as DWARF does, so that it does not depend
+        // on where they are found locally; that only matters for their content.
+        let mut sources = di.sources.clone();
+        let prefixes = PrefixReplacements::parse(&args.opt_strs("prefix"));
+        prefixes.replace_all(&mut sources);
+        convert_debug_info_to_json(&di, &sources, sources_content.as_deref()).to_string()
+    };
+    File::create(&output)
+        .and_then(|mut f_out| f_out.write_all(result.as_bytes()))
+        .map_err(|err| Error::File(output, err))?;
+    Ok(())
 }
 
 fn print_usage(program: &str, opts: Options) {
-    let brief = format!("Usage: {} [options] <INPUT>", program);
+    let brief = format!(
+        "Usage: {} [options] <INPUT>\n       {} [options] symbolize <INPUT> [OFFSET...]\n       {} [options] trace <INPUT>\n       {} [options] locate <INPUT> <FILE:LINE[:COLUMN]>...\n       {} linking <INPUT>",
+        program, program, program, program, program
+    );
     print!("{}", opts.usage(&brief));
     println!(
         "
@@ -182,6 +463,27 @@ Usage:
 
     # Read and convert to JSON
     wasm-dwarf foo.wasm -o foo.map
+
+    # Convert to JSON and reference the map from a copy of the wasm file
+    wasm-dwarf foo.wasm -o foo.map -w foo.out.wasm -m foo.map
+
+    # List call sites with their source lines
+    wasm-dwarf foo.wasm -c -o foo.calls
+
+    # List the first instruction of every line table row
+    wasm-dwarf foo.wasm -i lines -o foo.lines
+
+    # Print the function and source position of code offsets
+    wasm-dwarf symbolize foo.wasm 0x3f1a 1024
+
+    # Rewrite the wasm frames of a stack trace with source positions
+    wasm-dwarf trace foo.wasm < crash.txt
+
+    # Print the code ranges generated for source positions
+    wasm-dwarf locate foo.wasm src/lib.rs:12 src/lib.rs:40:9
+
+    # Dump the linking section of an object file
+    wasm-dwarf linking foo.o
 "
     );
 }
@@ -1,54 +1,152 @@
 // Reads wasm file debug sections contents.
 
 use std::collections::HashMap;
-use std::io::Write;
+use std::fmt;
 
-use dwarf::DebugLocInfo;
-use gimli::ReaderOffset;
-use wasmparser::{Operator, Parser, Payload::*};
+use dwarf::{DebugLocInfo, InlinedCall};
+use error::{Result, ResultExt, MODULE};
+use wasmparser::{CompositeType, FuncType, Operator, Parser, Payload::*, ValType};
+
+/// Selects the instructions that `map_source` lists.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ListingMode {
+    /// `call` and `call_indirect` instructions.
+    Calls,
+    /// The first instruction of every line table row.
+    Lines,
+    /// Every instruction.
+    Instructions,
+}
+
+// Operator variant name, e.g. "LocalGet" for `LocalGet { local_index: 0 }`.
+fn op_name(op: &Operator) -> String {
+    let name = format!("{:?}", op);
+    match name.find(|c: char| !c.is_alphanumeric()) {
+        Some(end) => name[..end].to_owned(),
+        None => name,
+    }
+}
+
+// Formats the signature as "(i32, i32) -> (i32)".
+fn format_signature(ty: &FuncType) -> String {
+    let join = |types: &[ValType]| {
+        let names: Vec<String> = types.iter().map(|ty| ty.to_string()).collect();
+        names.join(", ")
+    };
+    format!("({}) -> ({})", join(ty.params()), join(ty.results()))
+}
+
+/// The target of a call instruction.
+pub enum Callee<'a> {
+    /// A direct call of the function, with its name from the `name` section.
+    Direct {
+        function_index: u32,
+        name: Option<&'a str>,
+    },
+    /// A call through the table, with the signature of the type.
+    Indirect {
+        type_index: u32,
+        table_index: u32,
+        signature: Option<String>,
+    },
+}
+
+impl<'a> fmt::Display for Callee<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Callee::Direct {
+                function_index,
+                name,
+            } => {
+                write!(f, "${}", function_index)?;
+                if let Some(name) = name {
+                    write!(f, " {}", name)?;
+                }
+                Ok(())
+            }
+            Callee::Indirect {
+                type_index,
+                table_index,
+                signature,
+            } => {
+                write!(f, "table {} type {}", table_index, type_index)?;
+                if let Some(signature) = signature {
+                    write!(f, " {}", signature)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// An instruction and the source line it was generated from.
 pub struct SourceMapEntry<'a> {
     pub address: usize,
-    pub op: &'a str,
+    pub op: String,
+    /// The target of call instructions.
+    pub callee: Option<Callee<'a>>,
+    /// Inlined calls the instruction belongs to, innermost first.
+    pub inline_stack: Vec<&'a InlinedCall>,
     pub source_file: &'a String,
     pub line: usize,
+    /// 1-based column, or 0 for the left edge.
+    pub column: usize,
     pub source_code: &'a str,
 }
 
+/// Lists the instructions selected by `mode` with their source lines.
+/// `sources_content` holds the lines of every source of `debug_info`, and
+/// `func_names` the names of the called functions.
 pub fn map_source<'a>(
     wasm: &[u8],
     debug_info: &'a DebugLocInfo,
-    sources_content: &'a Vec<Vec<String>>,
-) -> Vec<SourceMapEntry<'a>> {
+    sources_content: &'a [Vec<String>],
+    func_names: &'a HashMap<u32, String>,
+    mode: ListingMode,
+) -> Result<Vec<SourceMapEntry<'a>>> {
     let parser = Parser::new(0);
 
     let debug_line = &debug_info.locations;
     let mut index = 0;
     let mut source_map = Vec::new();
+    let mut inline_stacks = debug_info.inline_stacks();
+    if debug_line.is_empty() {
+        return Ok(source_map);
+    }
     let move_forward = |curr: usize, till: usize| {
         let mut index = curr;
         while index + 1 < debug_line.len() && till >= debug_line[index + 1].address as usize {
             index += 1;
         }
-        return index;
+        index
     };
-    let mut code_section_count = 0;
+    // Signatures by type index; None for non-function types.
+    let mut signatures: Vec<Option<String>> = Vec::new();
     for payload in parser.parse_all(wasm) {
-        let payload = payload.unwrap();
+        let payload = payload.in_section(MODULE, 0)?;
 
-        match payload {
-            CodeSectionEntry(body) => 'walk_code_section: {
-                let curr_section = code_section_count;
-                code_section_count += 1;
-                let reader = body.get_operators_reader().unwrap();
+        if let TypeSection(reader) = payload {
+            for rec_group in reader {
+                for ty in rec_group.in_section(MODULE, 0)?.into_types() {
+                    signatures.push(match ty.composite_type {
+                        CompositeType::Func(ref func_type) => Some(format_signature(func_type)),
+                        _ => None,
+                    });
+                }
+            }
+        } else if let CodeSectionEntry(body) = payload {
+            'walk_code_section: {
+                let reader = body.get_operators_reader().in_section(MODULE, 0)?;
                 let start_position = reader.original_position();
-                let new_index = move_forward(index, start_position);
-                // the start of a code section should map to a new debug line.
-                if new_index == index {
-                    println!("function ${}'s debug line is missing.", curr_section);
+                index = move_forward(index, start_position);
+                // the start of a code section should map to a new debug line;
+                // functions without line information, including those before
+                // the first row, are not listed.
+                let address = debug_line[index].address as usize;
+                if address < body.range().start || address > start_position {
                     break 'walk_code_section;
                 }
-
-                index = new_index;
+                let mut last_index = None;
                 // println!(
                 //     "function ${} ({}:{})",
                 //     curr_section,
@@ -56,7 +154,7 @@ pub fn map_source<'a>(
                 //     debug_line[index].line
                 // );
                 for pair in reader.into_iter_with_offsets() {
-                    let (op, offset) = pair.unwrap();
+                    let (op, offset) = pair.in_section(MODULE, 0)?;
                     //     while index + 1 < debug_line.len()
                     //         && offset >= debug_line[index + 1].address as usize
                     //     {
@@ -72,22 +170,43 @@ pub fn map_source<'a>(
                     //         );
                     //     }
                     index = move_forward(index, offset);
-                    let op_name = match op {
-                        Operator::Call { function_index } => Some("Call"),
+                    let new_line = last_index != Some(index);
+                    last_index = Some(index);
+                    let callee = match op {
+                        Operator::Call { function_index }
+                        | Operator::ReturnCall { function_index } => Some(Callee::Direct {
+                            function_index,
+                            name: func_names.get(&function_index).map(|name| name.as_str()),
+                        }),
                         Operator::CallIndirect {
                             type_index,
                             table_index,
-                        } => Some("CallIndirect"),
+                        }
+                        | Operator::ReturnCallIndirect {
+                            type_index,
+                            table_index,
+                        } => Some(Callee::Indirect {
+                            type_index,
+                            table_index,
+                            signature: signatures.get(type_index as usize).cloned().flatten(),
+                        }),
                         _ => None,
                     };
-                    if op_name == None {
+                    let selected = match mode {
+                        ListingMode::Calls => callee.is_some(),
+                        ListingMode::Lines => new_line,
+                        ListingMode::Instructions => true,
+                    };
+                    if !selected {
                         continue;
                     }
                     let source_id = debug_line[index].source_id as usize;
                     let line = debug_line[index].line as usize;
-                    // let column = debug_line[index].column as usize;
+                    let column = debug_line[index].column as usize;
                     let source_content = &sources_content[source_id];
-                    let source_code = source_content[line - 1].trim();
+                    let source_code = source_content
+                        .get(line.wrapping_sub(1))
+                        .map_or("", |code| code.trim());
 
                     // println!(
                     //     "{}@{}\t\t{}({}:{})",
@@ -99,14 +218,16 @@ pub fn map_source<'a>(
                     // );
                     source_map.push(SourceMapEntry {
                         address: offset,
-                        op: op_name.unwrap(),
-                        line: line,
+                        op: op_name(&op),
+                        callee,
+                        inline_stack: inline_stacks.at(offset as u64),
+                        line,
+                        column,
                         source_file: &debug_info.sources[source_id],
-                        source_code: source_code,
+                        source_code,
                     });
                 }
             }
-            _ => {}
         }
     }
     // loop {
@@ -150,5 +271,52 @@ pub fn map_source<'a>(
     //         _ => {}
     //     }
     //}
-    source_map
+    Ok(source_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dwarf::DebugLoc;
+
+    fn loc(address: u64, line: u32, end_sequence: bool) -> DebugLoc {
+        DebugLoc {
+            address,
+            source_id: 0,
+            line,
+            column: 0,
+            name_id: None,
+            end_sequence,
+        }
+    }
+
+    #[test]
+    fn functions_before_the_first_row_are_skipped() {
+        // Two functions calling each other: the call of the first one is at
+        // 24, the call of the second one at 29.
+        let wasm = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x03, 0x02, 0x00, 0x00, 0x0a, 0x0b, 0x02, 0x04, 0x00, 0x10, 0x01, 0x0b, 0x04,
+            0x00, 0x10, 0x00, 0x0b,
+        ];
+        // Only the second function has line information.
+        let di = DebugLocInfo {
+            sources: vec![String::from("a.c")],
+            names: Vec::new(),
+            locations: vec![loc(28, 5, false), loc(32, 5, true)],
+            inlined_calls: Vec::new(),
+        };
+        let sources_content = vec![Vec::new()];
+        let func_names = HashMap::new();
+        let entries = map_source(
+            &wasm,
+            &di,
+            &sources_content,
+            &func_names,
+            ListingMode::Calls,
+        )
+        .unwrap();
+        let addresses: Vec<usize> = entries.iter().map(|entry| entry.address).collect();
+        assert_eq!(addresses, [29]);
+    }
 }
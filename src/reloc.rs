@@ -1,131 +1,404 @@
 // Applies reloction entries to the existing sections.
-use std::collections::HashMap;
-
 use wasmparser::{BinaryReader, WasmFeatures};
 
+use error::{Cause, Error, Result, ResultExt};
+use linking::{DataLocation, SymbolKind};
 use wasm_read::DebugSections;
 
-fn to_vec(b: &[u8]) -> Vec<u8> {
-    let mut result = Vec::new();
-    result.extend_from_slice(b);
-    result
+// How the value of a relocation is written at its offset. LEBs are padded
+// to their maximum width so the patched value never moves any byte.
+#[derive(Clone, Copy)]
+enum Encoding {
+    Uleb32,
+    Sleb32,
+    I32,
+    Uleb64,
+    Sleb64,
+    I64,
+}
+
+// What a relocation resolves to.
+#[derive(Clone, Copy)]
+enum Target {
+    FunctionIndex,
+    TableIndex,
+    MemoryAddress,
+    TypeIndex,
+    GlobalIndex,
+    FunctionOffset,
+    SectionOffset,
+    TagIndex,
+    TableNumber,
+    // Offset of a thread local symbol from the start of the TLS block.
+    TlsOffset,
+    // Memory address relative to the address of the relocated bytes.
+    LocationRelative,
 }
 
-enum SymbolKind {
-    Section(u32),
-    Data(u32, u32),
+// Decodes a relocation type of the tool conventions. The flag tells whether
+// the entry carries an addend.
+fn reloc_type(ty: u32) -> Option<(Target, Encoding, bool)> {
+    use self::Encoding::*;
+    use self::Target::*;
+    Some(match ty {
+        0 /* R_WASM_FUNCTION_INDEX_LEB */ => (FunctionIndex, Uleb32, false),
+        1 /* R_WASM_TABLE_INDEX_SLEB */ => (TableIndex, Sleb32, false),
+        2 /* R_WASM_TABLE_INDEX_I32 */ => (TableIndex, I32, false),
+        3 /* R_WASM_MEMORY_ADDR_LEB */ => (MemoryAddress, Uleb32, true),
+        4 /* R_WASM_MEMORY_ADDR_SLEB */ => (MemoryAddress, Sleb32, true),
+        5 /* R_WASM_MEMORY_ADDR_I32 */ => (MemoryAddress, I32, true),
+        6 /* R_WASM_TYPE_INDEX_LEB */ => (TypeIndex, Uleb32, false),
+        7 /* R_WASM_GLOBAL_INDEX_LEB */ => (GlobalIndex, Uleb32, false),
+        8 /* R_WASM_FUNCTION_OFFSET_I32 */ => (FunctionOffset, I32, true),
+        9 /* R_WASM_SECTION_OFFSET_I32 */ => (SectionOffset, I32, true),
+        10 /* R_WASM_TAG_INDEX_LEB */ => (TagIndex, Uleb32, false),
+        11 /* R_WASM_MEMORY_ADDR_REL_SLEB */ => (MemoryAddress, Sleb32, true),
+        12 /* R_WASM_TABLE_INDEX_REL_SLEB */ => (TableIndex, Sleb32, false),
+        13 /* R_WASM_GLOBAL_INDEX_I32 */ => (GlobalIndex, I32, false),
+        14 /* R_WASM_MEMORY_ADDR_LEB64 */ => (MemoryAddress, Uleb64, true),
+        15 /* R_WASM_MEMORY_ADDR_SLEB64 */ => (MemoryAddress, Sleb64, true),
+        16 /* R_WASM_MEMORY_ADDR_I64 */ => (MemoryAddress, I64, true),
+        17 /* R_WASM_MEMORY_ADDR_REL_SLEB64 */ => (MemoryAddress, Sleb64, true),
+        18 /* R_WASM_TABLE_INDEX_SLEB64 */ => (TableIndex, Sleb64, false),
+        19 /* R_WASM_TABLE_INDEX_I64 */ => (TableIndex, I64, false),
+        20 /* R_WASM_TABLE_NUMBER_LEB */ => (TableNumber, Uleb32, false),
+        21 /* R_WASM_MEMORY_ADDR_TLS_SLEB */ => (TlsOffset, Sleb32, true),
+        22 /* R_WASM_FUNCTION_OFFSET_I64 */ => (FunctionOffset, I64, true),
+        23 /* R_WASM_MEMORY_ADDR_LOCREL_I32 */ => (LocationRelative, I32, true),
+        24 /* R_WASM_TABLE_INDEX_REL_SLEB64 */ => (TableIndex, Sleb64, false),
+        25 /* R_WASM_MEMORY_ADDR_TLS_SLEB64 */ => (TlsOffset, Sleb64, true),
+        26 /* R_WASM_FUNCTION_INDEX_I32 */ => (FunctionIndex, I32, false),
+        _ => return None,
+    })
 }
 
-pub fn reloc(debug_sections: &mut DebugSections) {
-    let (func_indices, symbols) = {
-        let ref linking_table = debug_sections.linking.as_ref().unwrap();
-        let mut reader = BinaryReader::new(&linking_table, 0, WasmFeatures::all());
-        let version = reader.read_var_u32().unwrap();
-        assert!(version == 1);
-        let mut symbols: HashMap<u32, SymbolKind> = HashMap::new();
-        let mut func_indices: HashMap<u32, u32> = HashMap::new();
-        while !reader.eof() {
-            let table_code = reader.read_var_u32().unwrap();
-            let table = reader.read_string().unwrap().as_bytes();
-            if table_code == 0x8
-            /* WASM_SYMBOL_TABLE */
-            {
-                let mut table_reader = BinaryReader::new(table, 0, WasmFeatures::all());
-                let table_len = table_reader.read_var_u32().unwrap();
-                for index in 0..table_len {
-                    let symbol_kind = table_reader.read_var_u32().unwrap();
-                    let symbol_flags = table_reader.read_var_u32().unwrap();
-                    let wasm_symbol_undefined_flag = 0x10; /* _WASM_SYMBOL_UNDEFINED */
-                    // println!("k{}", symbol_kind);
-                    match symbol_kind {
-                        0x0 /* WASM_SYMBOL_TYPE_FUNCTION */ => {
-                            let elem_index = table_reader.read_var_u32().unwrap();
-                            if (symbol_flags & wasm_symbol_undefined_flag) == 0 {
-                                table_reader.read_string().unwrap();
-                            }
-                            func_indices.insert(index as u32, elem_index);
-                        }
-                        0x2 /* WASM_SYMBOL_TYPE_GLOBAL */ => {
-                            table_reader.read_var_u32().unwrap();
-                            if (symbol_flags & wasm_symbol_undefined_flag) == 0 {
-                                table_reader.read_string().unwrap();
-                            }
-                        }
-                        0x1 /* WASM_SYMBOL_TYPE_DATA */ => {
-                            table_reader.read_string().unwrap();
-                            if (symbol_flags & wasm_symbol_undefined_flag) == 0 {
-                                let data_segment = table_reader.read_var_u32().unwrap();
-                                let offset = table_reader.read_var_u32().unwrap();
-                                table_reader.read_var_u32().unwrap();
-
-                                symbols.insert(index as u32, SymbolKind::Data(data_segment, offset));
-                            }
-                        }
-                        0x3 /* WASM_SYMBOL_TYPE_SECTION */ => {
-                            let section_index = table_reader.read_var_u32().unwrap();
-                            symbols.insert(index as u32, SymbolKind::Section(section_index));
-                        }
-                        _ => panic!("unknown symbol kind")
-                    }
+// Writes `value` at `offset`. Returns false if the bytes do not fit in the
+// section.
+fn patch(table: &mut [u8], offset: usize, encoding: Encoding, value: i64) -> bool {
+    let width = match encoding {
+        Encoding::Uleb32 | Encoding::Sleb32 => 5,
+        Encoding::I32 => 4,
+        Encoding::Uleb64 | Encoding::Sleb64 => 10,
+        Encoding::I64 => 8,
+    };
+    let bytes = match offset
+        .checked_add(width)
+        .and_then(|end| table.get_mut(offset..end))
+    {
+        Some(bytes) => bytes,
+        None => return false,
+    };
+    match encoding {
+        Encoding::I32 | Encoding::I64 => {
+            bytes.copy_from_slice(&value.to_le_bytes()[..width]);
+        }
+        Encoding::Uleb32 | Encoding::Sleb32 | Encoding::Uleb64 | Encoding::Sleb64 => {
+            let mut value = match encoding {
+                Encoding::Uleb32 => value & 0xFFFF_FFFF,
+                _ => value,
+            };
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = (value & 0x7F) as u8;
+                if i + 1 < width {
+                    *byte |= 0x80;
                 }
+                // Arithmetic shift keeps the sign of SLEBs, unsigned values
+                // are never negative here.
+                value >>= 7;
             }
         }
-        (func_indices, symbols)
-    };
+    }
+    true
+}
 
-    let reloc_tables_names = {
-        let mut reloc_tables_names = Vec::new();
-        for key in debug_sections.reloc_tables.keys() {
-            reloc_tables_names.push(key.clone());
-        }
-        reloc_tables_names
-    };
+/// Applies the `reloc.*` entries to the debug, code and data sections of an
+/// object file, using the symbols from its `linking` section.
+pub fn reloc(debug_sections: &mut DebugSections) -> Result<()> {
+    let linking = debug_sections
+        .linking()?
+        .ok_or_else(|| Error::MissingSection(String::from("linking")))?;
 
-    for ref reloc_table_name in reloc_tables_names {
-        let reloc_table = debug_sections.reloc_tables[reloc_table_name].clone();
-        let fixup_section_name = &reloc_table_name[6..];
+    for (reloc_table_name, reloc_table) in debug_sections.reloc_tables.clone() {
         let mut reader = BinaryReader::new(&reloc_table, 0, WasmFeatures::all());
-        reader.read_var_u32().unwrap(); // TODO use section_index
-        let count = reader.read_var_u32().unwrap();
+        let section_index = reader.read_var_u32().in_section(reloc_table_name, 0)?;
+        let tables = &mut debug_sections.tables;
+        let (table, section_start) = match debug_sections
+            .tables_index
+            .get(&section_index)
+            .and_then(|(name, start)| tables.get_mut(name).map(|table| (table, *start)))
+        {
+            Some(target) => target,
+            // Sections that are not kept, such as other custom sections, do
+            // not need their relocations.
+            None => continue,
+        };
+        let count = reader.read_var_u32().in_section(reloc_table_name, 0)?;
         for _ in 0..count {
-            let ty = reader.read_var_u32().unwrap();
-            let mut table_entry = debug_sections.tables.get_mut(fixup_section_name);
-            let table: &mut Vec<u8> = table_entry.unwrap();
-            let fixup_offset = reader.read_var_u32().unwrap() as usize;
-
-            let index = reader.read_var_u32().unwrap();
-            let target_offset = match ty {
-                5 => {
-                    if let SymbolKind::Data(segment, offset) = symbols[&index] {
-                        debug_sections.data_segment_offsets[segment as usize] + offset
-                    } else {
-                        panic!("unexpected symbol type");
-                    }
+            let entry_start = reader.original_position();
+            let ty = reader.read_var_u32().in_section(reloc_table_name, 0)?;
+            let fixup_offset = reader.read_var_u32().in_section(reloc_table_name, 0)? as usize;
+            let index = reader.read_var_u32().in_section(reloc_table_name, 0)?;
+            let invalid = |cause| Error::invalid(reloc_table_name, entry_start, cause);
+            let (target, encoding, has_addend) =
+                reloc_type(ty).ok_or_else(|| invalid(Cause::UnknownRelocType(ty)))?;
+            let addend = match (has_addend, encoding) {
+                (false, _) => 0,
+                (true, Encoding::Uleb64) | (true, Encoding::Sleb64) | (true, Encoding::I64) => {
+                    reader.read_var_i64().in_section(reloc_table_name, 0)?
+                }
+                (true, _) => reader.read_var_i32().in_section(reloc_table_name, 0)? as i64,
+            };
+
+            let symbol = linking
+                .symbols
+                .get(index as usize)
+                .map(|symbol| symbol.kind);
+            let value = match (target, symbol) {
+                (Target::FunctionIndex, Some(SymbolKind::Function(func_index))) => {
+                    func_index as i64
+                }
+                // Table slots are assigned by the linker, the site keeps the
+                // value the producer wrote.
+                (Target::TableIndex, Some(SymbolKind::Function(_))) => continue,
+                (
+                    Target::MemoryAddress,
+                    Some(SymbolKind::Data(Some(DataLocation {
+                        segment, offset, ..
+                    }))),
+                ) => {
+                    let segment_offset = debug_sections
+                        .data_segment_offsets
+                        .get(segment as usize)
+                        .ok_or_else(|| invalid(Cause::InvalidIndex(segment)))?
+                        .constant()
+                        .ok_or_else(|| invalid(Cause::UnknownSegmentOffset(segment)))?;
+                    segment_offset as i64 + offset as i64 + addend
+                }
+                // Undefined data only gets an address when linked.
+                (Target::MemoryAddress, Some(SymbolKind::Data(None))) => continue,
+                // The index of a type relocation is the type itself.
+                (Target::TypeIndex, _) => index as i64,
+                (Target::GlobalIndex, Some(SymbolKind::Global(global_index))) => {
+                    global_index as i64
                 }
-                8 => {
-                    let func_index = func_indices.get(&index).unwrap();
-                    debug_sections.func_offsets[*func_index as usize] as u32 // function offset
+                // In position independent code, the global is the GOT entry
+                // of the data or function symbol, assigned by the linker.
+                (Target::GlobalIndex, Some(SymbolKind::Data(..)))
+                | (Target::GlobalIndex, Some(SymbolKind::Function(..))) => continue,
+                (Target::FunctionOffset, Some(SymbolKind::Function(func_index))) => {
+                    let func_offset = debug_sections
+                        .func_offsets
+                        .get(func_index as usize)
+                        .ok_or_else(|| invalid(Cause::InvalidIndex(func_index)))?;
+                    *func_offset as i64 + addend
                 }
-                9 => 0, // section offset,
-                _ => panic!("unexpected reloc type"),
+                // Offsets are relative to the start of the section, which
+                // may follow other sections of the same name in its table.
+                (Target::SectionOffset, Some(SymbolKind::Section(section))) => {
+                    let (_, start) = debug_sections
+                        .tables_index
+                        .get(&section)
+                        .ok_or_else(|| invalid(Cause::InvalidIndex(section)))?;
+                    *start as i64 + addend
+                }
+                (Target::TagIndex, Some(SymbolKind::Tag(tag_index))) => tag_index as i64,
+                (Target::TableNumber, Some(SymbolKind::Table(table_index))) => table_index as i64,
+                // TLS offsets and location relative addresses only appear in
+                // code and data, which are not mapped to sources; the site
+                // keeps the value the producer wrote.
+                (Target::TlsOffset, _) | (Target::LocationRelative, _) => continue,
+                _ => return Err(invalid(Cause::InvalidSymbol(index))),
             };
 
-            let target_addend = reader.read_var_u32().unwrap();
+            if !patch(table, section_start + fixup_offset, encoding, value) {
+                return Err(invalid(Cause::OutOfBounds(fixup_offset)));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_read::CODE;
 
-            let _old_offset = (table[fixup_offset + 0] as u32)
-                | ((table[fixup_offset + 1] as u32) << 8)
-                | ((table[fixup_offset + 2] as u32) << 16)
-                | ((table[fixup_offset + 3] as u32) << 24);
+    // Patches a 12 byte table of 0xaa at offset 1.
+    fn patched(encoding: Encoding, value: i64) -> Vec<u8> {
+        let mut table = vec![0xaa; 12];
+        assert!(patch(&mut table, 1, encoding, value));
+        table
+    }
 
-            let offset = target_offset + target_addend;
-            table[fixup_offset + 0] = (offset & 0xFF) as u8;
-            table[fixup_offset + 1] = ((offset >> 8) & 0xFF) as u8;
-            table[fixup_offset + 2] = ((offset >> 16) & 0xFF) as u8;
-            table[fixup_offset + 3] = ((offset >> 24) & 0xFF) as u8;
+    #[test]
+    fn patch_unsigned_lebs() {
+        assert_eq!(
+            patched(Encoding::Uleb32, -1),
+            [0xaa, 0xff, 0xff, 0xff, 0xff, 0x0f, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa]
+        );
+        // Only the low 32 bits are kept.
+        assert_eq!(
+            patched(Encoding::Uleb32, 0x1_0000_0001),
+            [0xaa, 0x81, 0x80, 0x80, 0x80, 0x00, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa]
+        );
+        assert_eq!(
+            patched(Encoding::Uleb64, 0x1_0000_0001),
+            [0xaa, 0x81, 0x80, 0x80, 0x80, 0x90, 0x80, 0x80, 0x80, 0x80, 0x00, 0xaa]
+        );
+    }
 
-            // println!("{:x}: {:x} -> {:x} ({} {} t{})",
-            //   fixup_offset, _old_offset, offset,
-            //   str::from_utf8(fixup_section_name).unwrap(), index, ty);
+    #[test]
+    fn patch_signed_lebs() {
+        assert_eq!(
+            patched(Encoding::Sleb32, -1),
+            [0xaa, 0xff, 0xff, 0xff, 0xff, 0x7f, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa]
+        );
+        assert_eq!(
+            patched(Encoding::Sleb32, -128),
+            [0xaa, 0x80, 0xff, 0xff, 0xff, 0x7f, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa]
+        );
+        assert_eq!(
+            patched(Encoding::Sleb64, -1),
+            [0xaa, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f, 0xaa]
+        );
+    }
+
+    #[test]
+    fn patch_integers() {
+        // Only the low 32 bits are kept.
+        assert_eq!(
+            patched(Encoding::I32, 0x1_0403_0201),
+            [0xaa, 0x01, 0x02, 0x03, 0x04, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa]
+        );
+        assert_eq!(
+            patched(Encoding::I64, 0x0807_0605_0403_0201),
+            [0xaa, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0xaa, 0xaa, 0xaa]
+        );
+    }
+
+    #[test]
+    fn patch_out_of_bounds() {
+        let mut table = vec![0xaa; 12];
+        assert!(!patch(&mut table, 8, Encoding::Uleb32, 0));
+        assert!(!patch(&mut table, 3, Encoding::Sleb64, 0));
+        assert!(!patch(&mut table, usize::MAX, Encoding::I32, 0));
+        assert_eq!(table, [0xaa; 12]);
+    }
+
+    fn custom_section(name: &str, payload: &[u8]) -> Vec<u8> {
+        let mut section = vec![0x00, (1 + name.len() + payload.len()) as u8];
+        section.push(name.len() as u8);
+        section.extend_from_slice(name.as_bytes());
+        section.extend_from_slice(payload);
+        section
+    }
+
+    // An object file with a function holding `i32.const` of a padded SLEB,
+    // a data segment at 0x100, and a `R_WASM_MEMORY_ADDR_SLEB` relocation of
+    // the constant against the only symbol.
+    fn object(linking_version: u8, symbol: &[u8]) -> Vec<u8> {
+        object_with_reloc(linking_version, symbol, 0x04)
+    }
+
+    // The same object file with another relocation type.
+    fn object_with_reloc(linking_version: u8, symbol: &[u8], reloc_type: u8) -> Vec<u8> {
+        let mut wasm = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        wasm.extend_from_slice(&[
+            0x0a, 0x0b, 0x01, 0x09, 0x00, 0x41, 0x80, 0x80, 0x80, 0x80, 0x00, 0x1a, 0x0b,
+        ]);
+        wasm.extend_from_slice(&[0x0b, 0x07, 0x01, 0x00, 0x41, 0x80, 0x02, 0x0b, 0x00]);
+        let mut linking = vec![linking_version, 0x08, 1 + symbol.len() as u8, 0x01];
+        linking.extend_from_slice(symbol);
+        wasm.extend(custom_section("linking", &linking));
+        wasm.extend(custom_section(
+            "reloc.CODE",
+            &[0x00, 0x01, reloc_type, 0x04, 0x00, 0x00],
+        ));
+        wasm
+    }
+
+    // Defined data symbol `x`, 4 bytes at offset 4 of segment 0.
+    const DEFINED_DATA: &[u8] = &[0x01, 0x00, 0x01, b'x', 0x00, 0x04, 0x04];
+    // Undefined data symbol `x`.
+    const UNDEFINED_DATA: &[u8] = &[0x01, 0x10, 0x01, b'x'];
+
+    // Symbol of the unknown kind 6.
+    const UNKNOWN_KIND: &[u8] = &[0x06, 0x00];
+
+    fn relocated_code(wasm: &[u8]) -> Vec<u8> {
+        let mut debug_sections = DebugSections::read_sections(wasm).unwrap();
+        reloc(&mut debug_sections).unwrap();
+        debug_sections.tables[CODE].clone()
+    }
+
+    #[test]
+    fn relocates_memory_address() {
+        for version in [1, 2] {
+            let code = relocated_code(&object(version, DEFINED_DATA));
+            assert_eq!(code[4..9], [0x84, 0x82, 0x80, 0x80, 0x00]);
         }
     }
+
+    #[test]
+    fn unsupported_version_fails_only_relocation() {
+        let wasm = object(3, DEFINED_DATA);
+        let mut debug_sections = DebugSections::read_sections(&wasm).unwrap();
+        assert!(matches!(
+            reloc(&mut debug_sections),
+            Err(Error::InvalidSection {
+                cause: Cause::UnsupportedVersion(3),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn skips_relocations_of_other_sections() {
+        let mut wasm = object(2, DEFINED_DATA);
+        // Relocations of the `linking` section, which is not kept.
+        wasm.extend(custom_section("reloc.__llvm_covfun", &[0x02, 0x00]));
+        let code = relocated_code(&wasm);
+        assert_eq!(code[4..9], [0x84, 0x82, 0x80, 0x80, 0x00]);
+    }
+
+    #[test]
+    fn keeps_address_of_undefined_data() {
+        let code = relocated_code(&object(2, UNDEFINED_DATA));
+        assert_eq!(code[4..9], [0x80, 0x80, 0x80, 0x80, 0x00]);
+    }
+
+    #[test]
+    fn invalid_linking_section_fails_only_relocation() {
+        let wasm = object(2, UNKNOWN_KIND);
+        let mut debug_sections = DebugSections::read_sections(&wasm).unwrap();
+        assert!(matches!(
+            reloc(&mut debug_sections),
+            Err(Error::InvalidSection {
+                cause: Cause::UnknownSymbolKind(6),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn tls_offset_keeps_other_relocations() {
+        // R_WASM_MEMORY_ADDR_TLS_SLEB in the code.
+        let mut wasm = object_with_reloc(2, DEFINED_DATA, 21);
+        // R_WASM_MEMORY_ADDR_I32 of section 4, `.debug_info`.
+        wasm.extend(custom_section(".debug_info", &[0x00; 4]));
+        wasm.extend(custom_section(
+            "reloc..debug_info",
+            &[0x04, 0x01, 0x05, 0x00, 0x00, 0x00],
+        ));
+        let mut debug_sections = DebugSections::read_sections(&wasm).unwrap();
+        reloc(&mut debug_sections).unwrap();
+        assert_eq!(
+            debug_sections.tables[CODE][4..9],
+            [0x80, 0x80, 0x80, 0x80, 0x00]
+        );
+        assert_eq!(
+            debug_sections.tables[".debug_info"],
+            [0x04, 0x01, 0x00, 0x00]
+        );
+    }
 }
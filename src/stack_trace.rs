@@ -0,0 +1,146 @@
+// Rewrites wasm frames of browser and Node stack traces with source positions.
+
+use dwarf::DebugLocInfo;
+
+const FRAME_MARKER: &str = "wasm-function[";
+
+// A `[url:]wasm-function[index]:0xoffset` frame location found in a line.
+// The offset is relative to the start of the module.
+#[derive(Debug, PartialEq)]
+struct FrameLocation {
+    start: usize,
+    end: usize,
+    offset: u64,
+}
+
+fn is_location_delimiter(c: char) -> bool {
+    c.is_whitespace() || c == '(' || c == '@'
+}
+
+// Finds the frame location whose marker starts at `marker`.
+fn parse_location(line: &str, marker: usize) -> Option<FrameLocation> {
+    let start = line[..marker]
+        .rfind(is_location_delimiter)
+        .map_or(0, |i| i + 1);
+    let rest = &line[marker + FRAME_MARKER.len()..];
+    let index_end = rest.find(']')?;
+    // The function index is not needed, the offset is in the module.
+    rest[..index_end].parse::<u32>().ok()?;
+    let rest = rest[index_end..].strip_prefix("]:0x")?;
+    let hex_len = rest
+        .find(|c: char| !c.is_ascii_hexdigit())
+        .unwrap_or(rest.len());
+    let offset = u64::from_str_radix(&rest[..hex_len], 16).ok()?;
+    let end = line.len() - rest.len() + hex_len;
+    Some(FrameLocation { start, end, offset })
+}
+
+/// Replaces the wasm frame locations of a stack trace line, as printed by
+/// Chrome, Firefox and Node (`wasm-function[12]:0x3f1a`, optionally
+/// preceded by the module URL), with `file:line:column`. Locations without
+/// line information are left as is.
+pub fn symbolize_stack_trace_line(line: &str, di: &DebugLocInfo) -> String {
+    let mut result = String::new();
+    let mut copied = 0;
+    let mut search_from = 0;
+    while let Some(found) = line[search_from..].find(FRAME_MARKER) {
+        let marker = search_from + found;
+        search_from = marker + FRAME_MARKER.len();
+        let location = match parse_location(line, marker) {
+            Some(location) if location.start >= copied => location,
+            _ => continue,
+        };
+        let loc = match di.lookup(location.offset) {
+            Some(loc) => loc,
+            None => continue,
+        };
+        result += &line[copied..location.start];
+        result += &format!(
+            "{}:{}:{}",
+            di.sources[loc.source_id as usize], loc.line, loc.column
+        );
+        copied = location.end;
+        search_from = location.end;
+    }
+    result += &line[copied..];
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dwarf::DebugLoc;
+
+    fn loc(address: u64, line: u32, column: u32, end_sequence: bool) -> DebugLoc {
+        DebugLoc {
+            address,
+            source_id: 0,
+            line,
+            column,
+            name_id: None,
+            end_sequence,
+        }
+    }
+
+    fn symbolize(line: &str) -> String {
+        let di = DebugLocInfo {
+            sources: vec![String::from("a.c")],
+            names: Vec::new(),
+            locations: vec![
+                loc(0x80, 3, 5, false),
+                loc(0x90, 7, 1, false),
+                loc(0xa0, 7, 1, true),
+            ],
+            inlined_calls: Vec::new(),
+        };
+        symbolize_stack_trace_line(line, &di)
+    }
+
+    #[test]
+    fn parse_frame_location() {
+        let line = "add@http://x/a.wasm:wasm-function[1]:0x8e)";
+        let marker = line.find(FRAME_MARKER).unwrap();
+        assert_eq!(
+            parse_location(line, marker),
+            Some(FrameLocation {
+                start: 4,
+                end: line.len() - 1,
+                offset: 0x8e
+            })
+        );
+        let line = "wasm-function[x]:0x8e";
+        assert_eq!(parse_location(line, 0), None);
+    }
+
+    #[test]
+    fn chrome_frame() {
+        assert_eq!(
+            symbolize("    at add (http://localhost:8000/a.wasm:wasm-function[1]:0x8e)"),
+            "    at add (a.c:3:5)"
+        );
+    }
+
+    #[test]
+    fn firefox_frame() {
+        assert_eq!(
+            symbolize("add@http://localhost:8000/a.wasm:wasm-function[1]:0x8e"),
+            "add@a.c:3:5"
+        );
+    }
+
+    #[test]
+    fn node_frame() {
+        assert_eq!(
+            symbolize("    at add (wasm://wasm/8c5e1a2b:wasm-function[1]:0x8e)"),
+            "    at add (a.c:3:5)"
+        );
+    }
+
+    #[test]
+    fn two_frames_and_unknown_offset() {
+        assert_eq!(
+            symbolize("wasm-function[1]:0x8e wasm-function[2]:0x9a wasm-function[3]:0xb0"),
+            "a.c:3:5 a.c:7:1 wasm-function[3]:0xb0"
+        );
+    }
+}
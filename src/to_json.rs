@@ -0,0 +1,171 @@
+// Converts DWARF line information into Source Map v3 JSON.
+
+use std::collections::BTreeMap;
+
+use dwarf::DebugLocInfo;
+use rustc_serialize::json::Json;
+use vlq;
+
+fn push_segment(mappings: &mut Vec<u8>, fields: &[i64]) {
+    if !mappings.is_empty() {
+        mappings.push(b',');
+    }
+    for field in fields {
+        vlq::encode(*field, mappings).expect("vlq written");
+    }
+}
+
+// Builds the "mappings" string. A wasm module is a single generated line,
+// so every segment lives on line 0 and its generated column is the
+// address of the instruction in the binary.
+fn encode_mappings(di: &DebugLocInfo) -> String {
+    let mut mappings = Vec::new();
+    let mut last_address: i64 = 0;
+    let mut last_source_id: i64 = 0;
+    let mut last_line: i64 = 0;
+    let mut last_column: i64 = 0;
+    let mut last_name_id: i64 = 0;
+    // Whether the last segment maps code to a source position.
+    let mut mapped = false;
+    for (i, loc) in di.locations.iter().enumerate() {
+        let next = di.locations.get(i + 1);
+        let address = loc.address as i64;
+        // End of sequence rows, and rows of compiler generated code without
+        // a source line, do not map their code.
+        let unmapped = loc.end_sequence || loc.line == 0;
+        if let Some(next) = next {
+            // Several rows can describe the same address, the last one is
+            // the most precise position.
+            if next.address == loc.address && (unmapped || next.line != 0) {
+                continue;
+            }
+        }
+        if unmapped {
+            // A segment with only the generated column ends the mapping of
+            // the previous row.
+            if mapped {
+                push_segment(&mut mappings, &[address - last_address]);
+                last_address = address;
+                mapped = false;
+            }
+            continue;
+        }
+        let source_id = loc.source_id as i64;
+        let line = loc.line as i64 - 1;
+        // DWARF columns are 1-based, with 0 meaning the left edge.
+        let column = loc.column.saturating_sub(1) as i64;
+        let mut segment = vec![
+            address - last_address,
+            source_id - last_source_id,
+            line - last_line,
+            column - last_column,
+        ];
+        if let Some(name_id) = loc.name_id {
+            segment.push(name_id as i64 - last_name_id);
+            last_name_id = name_id as i64;
+        }
+        push_segment(&mut mappings, &segment);
+        mapped = true;
+        last_address = address;
+        last_source_id = source_id;
+        last_line = line;
+        last_column = column;
+    }
+    String::from_utf8(mappings).unwrap()
+}
+
+/// Builds a Source Map v3 document from the line information. `sources`
+/// replaces the paths of `di.sources`; when given, `sources_content` holds
+/// the text of every source, or `None` if the source is not available.
+pub fn convert_debug_info_to_json(
+    di: &DebugLocInfo,
+    sources: &[String],
+    sources_content: Option<&[Option<String>]>,
+) -> Json {
+    let mut map = BTreeMap::new();
+    map.insert(String::from("version"), Json::U64(3));
+    map.insert(
+        String::from("sources"),
+        Json::Array(sources.iter().map(|s| Json::String(s.clone())).collect()),
+    );
+    if let Some(sources_content) = sources_content {
+        map.insert(
+            String::from("sourcesContent"),
+            Json::Array(
+                sources_content
+                    .iter()
+                    .map(|content| match content {
+                        Some(text) => Json::String(text.clone()),
+                        None => Json::Null,
+                    })
+                    .collect(),
+            ),
+        );
+    }
+    map.insert(
+        String::from("names"),
+        Json::Array(di.names.iter().map(|s| Json::String(s.clone())).collect()),
+    );
+    map.insert(String::from("mappings"), Json::String(encode_mappings(di)));
+    Json::Object(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dwarf::DebugLoc;
+
+    fn loc(address: u64, line: u32, end_sequence: bool) -> DebugLoc {
+        DebugLoc {
+            address,
+            source_id: 0,
+            line,
+            column: 0,
+            name_id: None,
+            end_sequence,
+        }
+    }
+
+    fn mappings(locations: Vec<DebugLoc>) -> String {
+        encode_mappings(&DebugLocInfo {
+            sources: vec![String::from("a.c")],
+            names: Vec::new(),
+            locations,
+            inlined_calls: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn end_of_sequence_ends_the_mapping() {
+        // Code at 0x10..0x20, a gap, and code again from 0x30.
+        let locations = vec![
+            loc(0x10, 1, false),
+            loc(0x20, 1, true),
+            loc(0x30, 2, false),
+            loc(0x40, 2, true),
+        ];
+        assert_eq!(mappings(locations), "gBAAA,gB,gBACA,gB");
+    }
+
+    #[test]
+    fn code_without_line_ends_the_mapping() {
+        let locations = vec![
+            loc(0x10, 1, false),
+            loc(0x18, 0, false),
+            loc(0x20, 2, false),
+            loc(0x30, 2, true),
+        ];
+        assert_eq!(mappings(locations), "gBAAA,Q,QACA,gB");
+    }
+
+    #[test]
+    fn adjacent_sequences_need_no_end() {
+        let locations = vec![
+            loc(0x10, 1, false),
+            loc(0x20, 1, true),
+            loc(0x20, 2, false),
+            loc(0x30, 2, true),
+        ];
+        assert_eq!(mappings(locations), "gBAAA,gBACA,gB");
+    }
+}
@@ -3,122 +3,424 @@
 use std::collections::HashMap;
 use std::io::Write;
 
+use error::{Cause, Error, Result, ResultExt, MODULE};
+use linking::{read_linking, Linking};
 use wasmparser::{
-    BinaryReader, Data, DataKind, KnownCustom, Operator, Parser, Payload::*, WasmFeatures,
+    BinaryReader, ConstExpr, Data, DataKind, IndirectNameMap, Name, NameMap, NameSectionReader,
+    Operator, Parser, Payload::*, TypeRef, WasmFeatures,
 };
 
-fn is_reloc_debug_section(name: &str) -> bool {
-    return name.starts_with("reloc..debug_");
+/// Key of the code section payload in `DebugSections::tables`.
+pub const CODE: &str = "CODE";
+/// Key of the data section payload in `DebugSections::tables`.
+pub const DATA: &str = "DATA";
+
+// Magic number and version of a wasm module.
+const MODULE_HEADER: &[u8] = b"\0asm\x01\0\0\0";
+
+fn is_reloc_section(name: &str) -> bool {
+    name.starts_with("reloc.")
 }
 
 fn is_debug_section(name: &str) -> bool {
-    return name.starts_with(".debug_");
+    name.starts_with(".debug_")
 }
 
 fn is_linking_section(name: &str) -> bool {
-    return name == "linking";
+    name == "linking"
 }
 
-// fn is_source_mapping_section(name: &str) -> bool {
-//     return name == "sourceMappingURL";
-// }
+fn is_source_mapping_section(name: &str) -> bool {
+    name == "sourceMappingURL"
+}
 
 fn is_name_section(name: &str) -> bool {
-    return name == "name";
+    name == "name"
 }
+/// Custom sections and code layout of a wasm module needed to read its
+/// DWARF data.
 pub struct DebugSections<'a> {
+    /// Contents of the `.debug_*` sections, keyed by section name, and of
+    /// the code and data sections, keyed by `CODE` and `DATA`. Sections
+    /// with the same name are concatenated, as a linker would do.
     pub tables: HashMap<&'a str, Vec<u8>>,
-    // pub tables_index: HashMap<usize, Vec<u8>>,
-    pub reloc_tables: HashMap<&'a str, Vec<u8>>,
-    pub linking: Option<Vec<u8>>,
+    /// Keys in `tables` of the sections, by their index in the module, and
+    /// the offset of their contents in the table.
+    pub tables_index: HashMap<u32, (&'a str, usize)>,
+    /// Names and contents of the `reloc.*` sections, in module order.
+    pub reloc_tables: Vec<(&'a str, Vec<u8>)>,
+    /// Contents of the `linking` section of an object file, decoded by
+    /// `linking` when needed.
+    pub linking_section: Option<Vec<u8>>,
+    /// Offset of the code section payload in the module.
     pub code_start: usize,
+    /// Offsets of the function bodies relative to `code_start`; imported
+    /// functions are recorded as 0.
     pub func_offsets: Vec<usize>,
-    pub data_segment_offsets: Vec<u32>,
+    /// Memory offsets of the active data segments.
+    pub data_segment_offsets: Vec<SegmentOffset>,
+    /// Contents of the `name` section, decoded by `names` when needed.
+    pub name_section: Option<Vec<u8>>,
+}
+/// Value of a data segment offset, or of a global, computed from its
+/// constant init expression.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SegmentOffset {
+    Constant(u64),
+    /// `addend` bytes past the value of an imported global that was not
+    /// given, e.g. `__memory_base` in position independent modules.
+    Relative {
+        global: u32,
+        addend: i64,
+    },
+}
+
+impl SegmentOffset {
+    /// The offset, if it does not depend on an unknown global.
+    pub fn constant(&self) -> Option<u64> {
+        match *self {
+            SegmentOffset::Constant(value) => Some(value),
+            SegmentOffset::Relative { .. } => None,
+        }
+    }
+}
+
+// Evaluates a constant expression, with the extended-const arithmetic.
+// `globals` holds the values of the globals defined so far, `None` for
+// those that are not an integer offset (e.g. `f64.const` or `ref.func`).
+fn eval_const_expr(expr: &ConstExpr, globals: &[Option<SegmentOffset>]) -> Result<SegmentOffset> {
+    use self::SegmentOffset::*;
+    let mut reader = expr.get_operators_reader();
+    let mut stack: Vec<SegmentOffset> = Vec::new();
+    loop {
+        let op_start = reader.original_position();
+        let unsupported = || Error::invalid(MODULE, op_start, Cause::UnsupportedInitExpr);
+        let op = reader.read().in_section(MODULE, 0)?;
+        let (wide, value) = match op {
+            Operator::I32Const { value } => {
+                stack.push(Constant(value as u32 as u64));
+                continue;
+            }
+            Operator::I64Const { value } => {
+                stack.push(Constant(value as u64));
+                continue;
+            }
+            Operator::GlobalGet { global_index } => {
+                let global = globals.get(global_index as usize).ok_or_else(|| {
+                    Error::invalid(MODULE, op_start, Cause::InvalidIndex(global_index))
+                })?;
+                stack.push(global.ok_or_else(unsupported)?);
+                continue;
+            }
+            Operator::End => break,
+            Operator::I32Add | Operator::I32Sub | Operator::I32Mul => {
+                let rhs = stack.pop().ok_or_else(unsupported)?;
+                let lhs = stack.pop().ok_or_else(unsupported)?;
+                (false, eval_binary(&op, lhs, rhs).ok_or_else(unsupported)?)
+            }
+            Operator::I64Add | Operator::I64Sub | Operator::I64Mul => {
+                let rhs = stack.pop().ok_or_else(unsupported)?;
+                let lhs = stack.pop().ok_or_else(unsupported)?;
+                (true, eval_binary(&op, lhs, rhs).ok_or_else(unsupported)?)
+            }
+            _ => return Err(unsupported()),
+        };
+        stack.push(match value {
+            Constant(value) if !wide => Constant(value as u32 as u64),
+            value => value,
+        });
+    }
+    match stack[..] {
+        [value] => Ok(value),
+        _ => Err(Error::invalid(
+            MODULE,
+            reader.original_position(),
+            Cause::UnsupportedInitExpr,
+        )),
+    }
 }
-fn parse_function_names(section: KnownCustom) -> HashMap<u32, String> {
-    let mut func_names = HashMap::new();
-    let reader = BinaryReader::new(data, 0, WasmFeatures::all());
 
-    func_names
+// Applies an add, sub or mul. Offsets relative to an unknown global only
+// support adding or subtracting constants, and the difference of two
+// offsets relative to the same global.
+fn eval_binary(op: &Operator, lhs: SegmentOffset, rhs: SegmentOffset) -> Option<SegmentOffset> {
+    use self::SegmentOffset::*;
+    Some(match (op, lhs, rhs) {
+        (Operator::I32Add, Constant(a), Constant(b))
+        | (Operator::I64Add, Constant(a), Constant(b)) => Constant(a.wrapping_add(b)),
+        (Operator::I32Sub, Constant(a), Constant(b))
+        | (Operator::I64Sub, Constant(a), Constant(b)) => Constant(a.wrapping_sub(b)),
+        (Operator::I32Mul, Constant(a), Constant(b))
+        | (Operator::I64Mul, Constant(a), Constant(b)) => Constant(a.wrapping_mul(b)),
+        (Operator::I32Add, Relative { global, addend }, Constant(c))
+        | (Operator::I64Add, Relative { global, addend }, Constant(c))
+        | (Operator::I32Add, Constant(c), Relative { global, addend })
+        | (Operator::I64Add, Constant(c), Relative { global, addend }) => Relative {
+            global,
+            addend: addend.wrapping_add(c as i64),
+        },
+        (Operator::I32Sub, Relative { global, addend }, Constant(c))
+        | (Operator::I64Sub, Relative { global, addend }, Constant(c)) => Relative {
+            global,
+            addend: addend.wrapping_sub(c as i64),
+        },
+        (
+            Operator::I32Sub,
+            Relative {
+                global: a,
+                addend: x,
+            },
+            Relative {
+                global: b,
+                addend: y,
+            },
+        )
+        | (
+            Operator::I64Sub,
+            Relative {
+                global: a,
+                addend: x,
+            },
+            Relative {
+                global: b,
+                addend: y,
+            },
+        ) if a == b => Constant(x.wrapping_sub(y) as u64),
+        _ => return None,
+    })
+}
+
+/// Contents of the `name` custom section. Maps are keyed by the index of
+/// the named item; indirect maps are keyed by the function (or type) index
+/// first.
+#[derive(Default)]
+pub struct Names {
+    pub module: Option<String>,
+    pub functions: HashMap<u32, String>,
+    pub locals: HashMap<u32, HashMap<u32, String>>,
+    pub labels: HashMap<u32, HashMap<u32, String>>,
+    pub types: HashMap<u32, String>,
+    pub tables: HashMap<u32, String>,
+    pub memories: HashMap<u32, String>,
+    pub globals: HashMap<u32, String>,
+    pub elements: HashMap<u32, String>,
+    pub data: HashMap<u32, String>,
+    pub fields: HashMap<u32, HashMap<u32, String>>,
+    pub tags: HashMap<u32, String>,
+}
+
+fn read_name_map(map: NameMap) -> Result<HashMap<u32, String>> {
+    let mut names = HashMap::new();
+    for naming in map {
+        let naming = naming.in_section(MODULE, 0)?;
+        names.insert(naming.index, naming.name.to_owned());
+    }
+    Ok(names)
 }
+
+fn read_indirect_name_map(map: IndirectNameMap) -> Result<HashMap<u32, HashMap<u32, String>>> {
+    let mut names = HashMap::new();
+    for naming in map {
+        let naming = naming.in_section(MODULE, 0)?;
+        names.insert(naming.index, read_name_map(naming.names)?);
+    }
+    Ok(names)
+}
+
+fn read_names(data: &[u8]) -> Result<Names> {
+    let reader = NameSectionReader::new(BinaryReader::new(data, 0, WasmFeatures::all()));
+    let mut names = Names::default();
+    for subsection in reader {
+        match subsection.in_section(MODULE, 0)? {
+            Name::Module { name, .. } => names.module = Some(name.to_owned()),
+            Name::Function(map) => names.functions = read_name_map(map)?,
+            Name::Local(map) => names.locals = read_indirect_name_map(map)?,
+            Name::Label(map) => names.labels = read_indirect_name_map(map)?,
+            Name::Type(map) => names.types = read_name_map(map)?,
+            Name::Table(map) => names.tables = read_name_map(map)?,
+            Name::Memory(map) => names.memories = read_name_map(map)?,
+            Name::Global(map) => names.globals = read_name_map(map)?,
+            Name::Element(map) => names.elements = read_name_map(map)?,
+            Name::Data(map) => names.data = read_name_map(map)?,
+            Name::Field(map) => names.fields = read_indirect_name_map(map)?,
+            Name::Tag(map) => names.tags = read_name_map(map)?,
+            Name::Unknown { .. } => {}
+        }
+    }
+    Ok(names)
+}
+
 impl<'a> DebugSections<'a> {
-    pub fn read_sections(wasm: &'a [u8]) -> DebugSections<'a> {
+    /// Names of the `.debug_*` sections found in the module, sorted.
+    pub fn debug_section_names(&self) -> Vec<&'a str> {
+        let mut names: Vec<&'a str> = self
+            .tables
+            .keys()
+            .cloned()
+            .filter(|name| is_debug_section(name))
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Decodes the `linking` section, if the module has one.
+    pub fn linking(&self) -> Result<Option<Linking>> {
+        self.linking_section
+            .as_ref()
+            .map(|data| read_linking(data))
+            .transpose()
+    }
+
+    /// Decodes the `name` section; a module without one has no names.
+    pub fn names(&self) -> Result<Names> {
+        match self.name_section {
+            Some(ref data) => read_names(data),
+            None => Ok(Names::default()),
+        }
+    }
+
+    /// Collects the debug sections and code layout of the wasm module.
+    pub fn read_sections(wasm: &'a [u8]) -> Result<DebugSections<'a>> {
+        DebugSections::read_sections_with_globals(wasm, &HashMap::new())
+    }
+
+    /// Like `read_sections`, with the values of imported globals, such as
+    /// `__memory_base`, keyed by their import or `name` section name.
+    /// Segment offsets that depend on other imported globals are kept as
+    /// `SegmentOffset::Relative`.
+    pub fn read_sections_with_globals(
+        wasm: &'a [u8],
+        global_values: &HashMap<String, u64>,
+    ) -> Result<DebugSections<'a>> {
         let parser = Parser::new(0);
-        let mut linking: Option<Vec<u8>> = None;
+        let mut linking_section = None;
         let mut tables = HashMap::new();
-        // let mut tables_index = HashMap::new();
-        let mut reloc_tables = HashMap::new();
+        let mut tables_index = HashMap::new();
+        let mut reloc_tables = Vec::new();
         let mut code_start: usize = 0;
         let mut func_offsets = Vec::new();
         let mut data_segment_offsets = Vec::new();
-        // let mut section_index = 0;
+        let mut globals = Vec::new();
+        let mut global_imports = HashMap::new();
+        let mut name_section = None;
+        let mut section_index = 0;
         for payload in parser.parse_all(wasm) {
-            let payload = payload.unwrap();
+            let payload = payload.in_section(MODULE, 0)?;
+            let is_section = payload.as_section().is_some();
             match payload {
                 CustomSection(reader) => {
                     let name = reader.name();
                     let data = reader.data();
-                    if is_debug_section(&name) {
-                        tables.insert(name, data.to_vec());
-                    } else if is_reloc_debug_section(&name) {
-                        reloc_tables.insert(name, data.to_vec());
-                    } else if is_linking_section(&name) {
-                        linking = Some(data.to_vec());
+                    if is_debug_section(name) {
+                        let table: &mut Vec<u8> = tables.entry(name).or_default();
+                        tables_index.insert(section_index, (name, table.len()));
+                        table.extend_from_slice(data);
+                    } else if is_reloc_section(name) {
+                        reloc_tables.push((name, data.to_vec()));
+                    } else if is_linking_section(name) {
+                        linking_section = Some(data.to_vec());
                     } else if is_name_section(name) {
-                        let names = parse_function_names(reader.as_known());
+                        name_section = Some(data.to_vec());
                     }
                 }
-                CodeSectionStart { count, range, size } => {
+                CodeSectionStart { range, .. } => {
                     code_start = range.start;
+                    tables.insert(CODE, wasm[range].to_vec());
+                    tables_index.insert(section_index, (CODE, 0));
                 }
                 CodeSectionEntry(body) => {
                     let reader = body.get_binary_reader();
 
                     func_offsets.push(reader.original_position() - code_start);
                 }
-                ImportSection(reader) => func_offsets.push(0),
+                ImportSection(reader) => {
+                    for import in reader {
+                        let import = import.in_section(MODULE, 0)?;
+                        match import.ty {
+                            TypeRef::Func(_) => func_offsets.push(0),
+                            TypeRef::Global(_) => {
+                                let global = globals.len() as u32;
+                                global_imports.insert(global, import.name);
+                                globals.push(Some(SegmentOffset::Relative { global, addend: 0 }));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                GlobalSection(reader) => {
+                    for global in reader {
+                        let global = global.in_section(MODULE, 0)?;
+                        // Only an error if a data segment offset uses it.
+                        let value = eval_const_expr(&global.init_expr, &globals).ok();
+                        globals.push(value);
+                    }
+                }
                 DataSection(reader) => {
+                    tables.insert(DATA, wasm[reader.range()].to_vec());
+                    tables_index.insert(section_index, (DATA, 0));
                     for data in reader.into_iter() {
-                        let Data { kind, .. } = data.unwrap();
+                        let Data { kind, .. } = data.in_section(MODULE, 0)?;
                         if let DataKind::Active { offset_expr, .. } = kind {
-                            let mut op_reader = offset_expr.get_operators_reader();
-                            let op = op_reader.read().unwrap();
-                            if let Operator::I32Const { value } = op {
-                                data_segment_offsets.push(value as u32);
-                            } else {
-                                panic!("Unexpected init expression operator");
-                            }
+                            data_segment_offsets.push(eval_const_expr(&offset_expr, &globals)?);
                         }
                     }
                 }
                 _ => {}
             }
+            if is_section {
+                section_index += 1;
+            }
         }
 
-        DebugSections {
+        // The name section follows the data section, so globals are only
+        // resolved by name now. A malformed name section leaves them unnamed.
+        let global_names = name_section
+            .as_ref()
+            .and_then(|data| read_names(data).ok())
+            .map(|names| names.globals)
+            .unwrap_or_default();
+        for offset in data_segment_offsets.iter_mut() {
+            if let SegmentOffset::Relative { global, addend } = *offset {
+                let value = global_imports
+                    .get(&global)
+                    .and_then(|name| global_values.get(*name))
+                    .or_else(|| {
+                        global_names
+                            .get(&global)
+                            .and_then(|name| global_values.get(name))
+                    });
+                if let Some(value) = value {
+                    *offset = SegmentOffset::Constant(value.wrapping_add(addend as u64));
+                }
+            }
+        }
+
+        Ok(DebugSections {
             tables,
-            // tables_index,
+            tables_index,
             reloc_tables,
-            linking,
+            linking_section,
             code_start,
             func_offsets,
             data_segment_offsets,
-        }
+            name_section,
+        })
     }
 }
 
 fn convert_to_leb(n: usize) -> Vec<u8> {
     let mut buf = Vec::new();
     let mut i = n;
-    while i > 128 {
-        buf.push(0x80 | (n & 0x7f) as u8);
-        i = i >> 7;
+    while i >= 128 {
+        buf.push(0x80 | (i & 0x7f) as u8);
+        i >>= 7;
     }
     buf.push(i as u8);
     buf
 }
 
-pub fn add_source_mapping_url_section(url: &str, write: &mut Write) {
+/// Writes a `sourceMappingURL` custom section that points to `url`.
+pub fn add_source_mapping_url_section(url: &str, write: &mut dyn Write) -> Result<()> {
     let name = b"sourceMappingURL";
     let mut result = Vec::new();
     let custom_section_id = convert_to_leb(0);
@@ -131,5 +433,207 @@ pub fn add_source_mapping_url_section(url: &str, write: &mut Write) {
     result.extend_from_slice(name);
     result.extend_from_slice(&url_size);
     result.extend_from_slice(url.as_bytes());
-    write.write(&result).expect("wasm result written");
+    write.write_all(&result)?;
+    Ok(())
+}
+
+/// Copies the wasm module, dropping existing `sourceMappingURL` sections,
+/// and appends a new one that points to `url`.
+pub fn write_with_source_mapping_url(wasm: &[u8], url: &str, write: &mut dyn Write) -> Result<()> {
+    let header_len = MODULE_HEADER.len();
+    if !wasm.starts_with(MODULE_HEADER) {
+        return Err(Error::invalid(MODULE, 0, Cause::InvalidHeader));
+    }
+    write.write_all(&wasm[..header_len])?;
+    let mut reader = BinaryReader::new(&wasm[header_len..], header_len, WasmFeatures::all());
+    while !reader.eof() {
+        let section_start = reader.original_position() - header_len;
+        let id = reader.read_u8().in_section(MODULE, 0)?;
+        let size = reader.read_var_u32().in_section(MODULE, 0)? as usize;
+        let payload_start = reader.original_position();
+        let payload = reader.read_bytes(size).in_section(MODULE, 0)?;
+        if id == 0 {
+            let mut name_reader = BinaryReader::new(payload, payload_start, WasmFeatures::all());
+            let name = name_reader.read_string().in_section(MODULE, 0)?;
+            if is_source_mapping_section(name) {
+                continue;
+            }
+        }
+        let section_end = reader.original_position();
+        write.write_all(&wasm[header_len + section_start..section_end])?;
+    }
+    add_source_mapping_url_section(url, write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A module with a memory, an `f64` global and one active data segment
+    // placed by `offset_expr`.
+    fn module_with_f64_global(offset_expr: &[u8]) -> Vec<u8> {
+        let mut wasm = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        wasm.extend_from_slice(&[0x05, 0x03, 0x01, 0x00, 0x01]);
+        wasm.extend_from_slice(&[0x06, 0x0d, 0x01, 0x7c, 0x00, 0x44]);
+        wasm.extend_from_slice(&[0; 8]);
+        wasm.push(0x0b);
+        let mut data = vec![0x01, 0x00];
+        data.extend_from_slice(offset_expr);
+        data.push(0x00);
+        wasm.push(0x0b);
+        wasm.push(data.len() as u8);
+        wasm.extend_from_slice(&data);
+        wasm
+    }
+
+    // A module importing the `i32` global `env.g`, with a memory and one
+    // active data segment placed by `offset_expr`. The name section names
+    // the global `global_name`, if given.
+    fn module_with_imported_global(offset_expr: &[u8], global_name: Option<&str>) -> Vec<u8> {
+        let mut wasm = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        wasm.extend_from_slice(&[0x02, 0x0a, 0x01, 0x03, b'e', b'n', b'v', 0x01, b'g', 0x03]);
+        wasm.extend_from_slice(&[0x7f, 0x00]);
+        wasm.extend_from_slice(&[0x05, 0x03, 0x01, 0x00, 0x01]);
+        let mut data = vec![0x01, 0x00];
+        data.extend_from_slice(offset_expr);
+        data.push(0x00);
+        wasm.push(0x0b);
+        wasm.push(data.len() as u8);
+        wasm.extend_from_slice(&data);
+        if let Some(global_name) = global_name {
+            let mut names = vec![0x07, 3 + global_name.len() as u8, 0x01, 0x00];
+            names.push(global_name.len() as u8);
+            names.extend_from_slice(global_name.as_bytes());
+            wasm.extend_from_slice(&[0x00, 5 + names.len() as u8, 0x04, b'n', b'a', b'm', b'e']);
+            wasm.extend_from_slice(&names);
+        }
+        wasm
+    }
+
+    // `global.get 0` `i32.const 16` `i32.add`
+    const GLOBAL_PLUS_16: &[u8] = &[0x23, 0x00, 0x41, 0x10, 0x6a, 0x0b];
+
+    fn segment_offsets(wasm: &[u8], globals: &[(&str, u64)]) -> Vec<SegmentOffset> {
+        let global_values = globals
+            .iter()
+            .map(|(name, value)| (String::from(*name), *value))
+            .collect();
+        DebugSections::read_sections_with_globals(wasm, &global_values)
+            .unwrap()
+            .data_segment_offsets
+    }
+
+    #[test]
+    fn segment_offset_relative_to_imported_global() {
+        let wasm = module_with_imported_global(GLOBAL_PLUS_16, None);
+        assert_eq!(
+            segment_offsets(&wasm, &[]),
+            [SegmentOffset::Relative {
+                global: 0,
+                addend: 16
+            }]
+        );
+        assert_eq!(
+            segment_offsets(&wasm, &[("g", 0x400)]),
+            [SegmentOffset::Constant(0x410)]
+        );
+    }
+
+    #[test]
+    fn global_value_by_name_section_name() {
+        let wasm = module_with_imported_global(GLOBAL_PLUS_16, Some("__memory_base"));
+        assert_eq!(
+            segment_offsets(&wasm, &[("__memory_base", 0x400)]),
+            [SegmentOffset::Constant(0x410)]
+        );
+    }
+
+    #[test]
+    fn i32_arithmetic_wraps() {
+        // `i32.const -1` `i32.const 2` `i32.add`
+        let wasm = module_with_imported_global(&[0x41, 0x7f, 0x41, 0x02, 0x6a, 0x0b], None);
+        assert_eq!(segment_offsets(&wasm, &[]), [SegmentOffset::Constant(1)]);
+    }
+
+    #[test]
+    fn binary_operations_on_relative_offsets() {
+        use self::SegmentOffset::*;
+        let relative = |global, addend| Relative { global, addend };
+        assert_eq!(
+            eval_binary(&Operator::I32Add, Constant(1), relative(0, 4)),
+            Some(relative(0, 5))
+        );
+        assert_eq!(
+            eval_binary(&Operator::I64Sub, relative(0, 4), Constant(8)),
+            Some(relative(0, -4))
+        );
+        assert_eq!(
+            eval_binary(&Operator::I32Sub, relative(0, 16), relative(0, 4)),
+            Some(Constant(12))
+        );
+        assert_eq!(
+            eval_binary(&Operator::I32Sub, relative(0, 16), relative(1, 4)),
+            None
+        );
+        assert_eq!(
+            eval_binary(&Operator::I32Sub, Constant(16), relative(0, 4)),
+            None
+        );
+        assert_eq!(
+            eval_binary(&Operator::I64Mul, relative(0, 4), Constant(2)),
+            None
+        );
+    }
+
+    #[test]
+    fn non_integer_globals_are_ignored() {
+        let wasm = module_with_f64_global(&[0x41, 0x10, 0x0b]);
+        let debug_sections = DebugSections::read_sections(&wasm).unwrap();
+        assert_eq!(
+            debug_sections.data_segment_offsets,
+            vec![SegmentOffset::Constant(16)]
+        );
+    }
+
+    #[test]
+    fn segment_offset_from_non_integer_global_is_rejected() {
+        let wasm = module_with_f64_global(&[0x23, 0x00, 0x0b]);
+        assert!(matches!(
+            DebugSections::read_sections(&wasm),
+            Err(Error::InvalidSection {
+                cause: Cause::UnsupportedInitExpr,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn source_mapping_url_needs_module_header() {
+        for wasm in [
+            &b""[..],
+            b"\0asm",
+            b"\0asm\x0d\0\x01\0",
+            b"\0wasm\x01\0\0\0",
+        ] {
+            let mut out = Vec::new();
+            assert!(matches!(
+                write_with_source_mapping_url(wasm, "a.map", &mut out),
+                Err(Error::InvalidSection {
+                    cause: Cause::InvalidHeader,
+                    ..
+                })
+            ));
+            assert!(out.is_empty());
+        }
+    }
+
+    #[test]
+    fn malformed_name_section_is_reported_by_names() {
+        let mut wasm = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        // Function names, with a count of 2 and a single entry.
+        wasm.extend_from_slice(&[0x00, 0x0b, 0x04, b'n', b'a', b'm', b'e']);
+        wasm.extend_from_slice(&[0x01, 0x04, 0x02, 0x00, 0x01, b'f']);
+        let debug_sections = DebugSections::read_sections(&wasm).unwrap();
+        assert!(debug_sections.names().is_err());
+    }
 }